@@ -0,0 +1,266 @@
+use crate::convert::Luminance;
+use std::str::FromStr;
+
+// the background is estimated on the brightest luminance of every block of this size
+const BLOCK: usize = 8;
+// blocks this much darker than the first polynomial fit are left out of the second
+const OUTLIER: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flatten {
+    // morphological closing with a kernel of this many pixels
+    Closing(usize),
+    // least squares polynomial surface of this degree
+    Polynomial(usize),
+}
+
+impl FromStr for Flatten {
+    type Err = String;
+
+    // closing[:size] or polynomial[:degree]
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (method, parameter) = value.split_once(':').unwrap_or((value, ""));
+        let parameter = match parameter.trim() {
+            "" => None,
+            parameter => Some(parameter.parse::<usize>().map_err(|err| err.to_string())?),
+        };
+        match method.trim() {
+            "closing" => Ok(Self::Closing(parameter.unwrap_or(151))),
+            "polynomial" => Ok(Self::Polynomial(parameter.unwrap_or(2))),
+            _ => Err(format!(
+                "expected closing[:size] or polynomial[:degree], got {value}"
+            )),
+        }
+    }
+}
+
+pub struct Background {
+    grid_width: usize,
+    grid_height: usize,
+    grid: Vec<f64>,
+}
+
+impl Background {
+    pub fn estimate(width: usize, height: usize, pixels: &[[u8; 3]], flatten: Flatten) -> Self {
+        let grid_width = width.div_ceil(BLOCK).max(1);
+        let grid_height = height.div_ceil(BLOCK).max(1);
+        let mut grid = vec![0.0; grid_width * grid_height];
+        for (idx, [r, g, b]) in pixels.iter().enumerate() {
+            let block = idx / width / BLOCK * grid_width + idx % width / BLOCK;
+            let luminance = Luminance::Rec601.apply(*r as f64, *g as f64, *b as f64);
+            grid[block] = f64::max(grid[block], luminance);
+        }
+
+        let mut background = Self {
+            grid_width,
+            grid_height,
+            grid,
+        };
+        match flatten {
+            Flatten::Closing(size) => {
+                let radius = (size / BLOCK / 2).max(1);
+                background.filter(radius, f64::max);
+                background.filter(radius, f64::min);
+            }
+            Flatten::Polynomial(degree) => background.fit(degree),
+        }
+        background
+    }
+
+    // a separable max or min filter over the grid
+    fn filter(&mut self, radius: usize, select: fn(f64, f64) -> f64) {
+        let (width, height) = (self.grid_width, self.grid_height);
+        let mut result = self.grid.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let mut value = self.grid[y * width + x];
+                for other in x.saturating_sub(radius)..(x + radius + 1).min(width) {
+                    value = select(value, self.grid[y * width + other]);
+                }
+                result[y * width + x] = value;
+            }
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let mut value = result[y * width + x];
+                for other in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+                    value = select(value, result[other * width + x]);
+                }
+                self.grid[y * width + x] = value;
+            }
+        }
+    }
+
+    fn fit(&mut self, degree: usize) {
+        let (width, height) = (self.grid_width, self.grid_height);
+        let terms = |x: usize, y: usize| {
+            let x = x as f64 / (width.max(2) - 1) as f64 * 2.0 - 1.0;
+            let y = y as f64 / (height.max(2) - 1) as f64 * 2.0 - 1.0;
+            (0..=degree)
+                .flat_map(|i| (0..=degree - i).map(move |j| x.powi(i as i32) * y.powi(j as i32)))
+                .collect::<Vec<f64>>()
+        };
+        let all_terms = (0..width * height)
+            .map(|idx| terms(idx % width, idx / width))
+            .collect::<Vec<Vec<f64>>>();
+
+        let values = self.grid.clone();
+        let mut used = vec![true; values.len()];
+        for _ in 0..2 {
+            let Some(coefficients) = least_squares(&all_terms, &values, &used) else {
+                break;
+            };
+            for (idx, terms) in all_terms.iter().enumerate() {
+                self.grid[idx] = terms.iter().zip(&coefficients).map(|(t, c)| t * c).sum();
+                used[idx] = values[idx] >= self.grid[idx] * OUTLIER;
+            }
+        }
+    }
+
+    // the luminance of the background at a pixel, interpolated between the centers of the blocks
+    pub fn get(&self, x: usize, y: usize) -> f64 {
+        let position = |pixel: usize, size: usize| {
+            let position =
+                ((pixel as f64 + 0.5) / BLOCK as f64 - 0.5).clamp(0.0, (size - 1) as f64);
+            let low = position.floor() as usize;
+            (low, (low + 1).min(size - 1), position - low as f64)
+        };
+        let (x0, x1, tx) = position(x, self.grid_width);
+        let (y0, y1, ty) = position(y, self.grid_height);
+        let at = |x: usize, y: usize| self.grid[y * self.grid_width + x];
+        let top = at(x0, y0) * (1.0 - tx) + at(x1, y0) * tx;
+        let bottom = at(x0, y1) * (1.0 - tx) + at(x1, y1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    // divides the background out, so that it gets as bright as white, all channels by the same
+    // factor to keep the hues of the colors that are told apart afterwards
+    pub fn apply(&self, width: usize, pixels: &mut [[u8; 3]]) {
+        for (idx, pixel) in pixels.iter_mut().enumerate() {
+            let background = self.get(idx % width, idx / width);
+            if background > 0.0 {
+                for channel in pixel.iter_mut() {
+                    *channel = (*channel as f64 * 255.0 / background).round().min(255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+pub fn flatten(width: usize, height: usize, pixels: &mut [[u8; 3]], flatten: Flatten) {
+    Background::estimate(width, height, pixels, flatten).apply(width, pixels);
+}
+
+// solves the normal equations of the used rows with gaussian elimination
+fn least_squares(terms: &[Vec<f64>], values: &[f64], used: &[bool]) -> Option<Vec<f64>> {
+    let n = terms.first()?.len();
+    let mut matrix = vec![vec![0.0; n + 1]; n];
+    for ((terms, value), _) in terms
+        .iter()
+        .zip(values)
+        .zip(used)
+        .filter(|(_, used)| **used)
+    {
+        for (row, a) in matrix.iter_mut().zip(terms) {
+            for (cell, b) in row.iter_mut().zip(terms) {
+                *cell += a * b;
+            }
+            row[n] += a * value;
+        }
+    }
+    for column in 0..n {
+        let pivot = (column..n).max_by(|a, b| {
+            matrix[*a][column]
+                .abs()
+                .total_cmp(&matrix[*b][column].abs())
+        })?;
+        if matrix[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        let pivot = matrix[column].clone();
+        for (idx, row) in matrix.iter_mut().enumerate() {
+            if idx != column {
+                let factor = row[column] / pivot[column];
+                for (cell, value) in row.iter_mut().zip(&pivot).skip(column) {
+                    *cell -= factor * value;
+                }
+            }
+        }
+    }
+    Some(
+        matrix
+            .iter()
+            .enumerate()
+            .map(|(idx, row)| row[n] / row[idx])
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // yellowish paper that gets darker to the right with a dark stroke in the middle
+    fn shaded() -> (usize, usize, Vec<[u8; 3]>) {
+        let (width, height) = (256, 32);
+        let pixels = (0..width * height)
+            .map(|idx| {
+                let shade = 250.0 - 80.0 * (idx % width) as f64 / width as f64;
+                if idx / width == 16 {
+                    [20; 3]
+                } else {
+                    [shade as u8, shade as u8, (shade * 0.8) as u8]
+                }
+            })
+            .collect();
+        (width, height, pixels)
+    }
+
+    // as bright as white and still as yellow as before
+    fn whitened(pixel: [u8; 3]) -> bool {
+        let [r, g, b] = pixel.map(|channel| channel as f64);
+        Luminance::Rec601.apply(r, g, b) >= 245.0 && r == g && (b / r - 0.8).abs() < 0.02
+    }
+
+    #[test]
+    fn closing_whitens_paper() {
+        let (width, height, mut pixels) = shaded();
+        flatten(width, height, &mut pixels, Flatten::Closing(31));
+        assert!(whitened(pixels[width - 1]));
+        assert!(pixels[16 * width + 5].iter().all(|channel| *channel < 40));
+    }
+
+    #[test]
+    fn polynomial_whitens_paper() {
+        let (width, height, mut pixels) = shaded();
+        flatten(width, height, &mut pixels, Flatten::Polynomial(2));
+        assert!(whitened(pixels[width - 1]));
+        assert!(whitened(pixels[31 * width]));
+    }
+
+    #[test]
+    fn highlighter_keeps_its_color() {
+        // a highlighted area much wider than the kernel on gray paper
+        let (width, height) = (256, 32);
+        let pixels = (0..width * height)
+            .map(|idx| match idx % width {
+                64..192 => [254, 218, 13],
+                _ => [200; 3],
+            })
+            .collect::<Vec<[u8; 3]>>();
+        for method in [Flatten::Closing(31), Flatten::Polynomial(2)] {
+            let mut pixels = pixels.clone();
+            flatten(width, height, &mut pixels, method);
+            let [r, g, b] = pixels[16 * width + 128];
+            assert!(r >= 250 && g >= 210 && b < 30, "{method:?} {r},{g},{b}");
+        }
+    }
+
+    #[test]
+    fn parse_flatten() {
+        assert_eq!("closing".parse(), Ok(Flatten::Closing(151)));
+        assert_eq!("polynomial:3".parse(), Ok(Flatten::Polynomial(3)));
+        assert!("blur".parse::<Flatten>().is_err());
+    }
+}
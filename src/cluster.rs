@@ -0,0 +1,163 @@
+use crate::decoder::color_distance;
+use crate::gray::Pixels;
+
+const BITS: usize = 5;
+const ITERATIONS: usize = 20;
+
+// the colors reduced to 5 bits per channel, every bin keeps the sum of its colors for their mean
+pub struct ColorHistogram {
+    bins: Vec<(usize, [f64; 3])>,
+}
+
+impl ColorHistogram {
+    pub fn new(pixels: Pixels) -> Self {
+        let mut histogram = Self {
+            bins: vec![(0, [0.0; 3]); 1 << (3 * BITS)],
+        };
+        match pixels {
+            Pixels::Rgb(pixels) => {
+                for pixel in pixels {
+                    histogram.add(pixel, 1);
+                }
+            }
+            Pixels::Indexed(palette, indices) => {
+                let mut counts = vec![0; palette.len() / 3];
+                for index in indices {
+                    counts[index as usize] += 1;
+                }
+                for (rgb, count) in palette.chunks(3).zip(counts) {
+                    histogram.add([rgb[0] as f64, rgb[1] as f64, rgb[2] as f64], count);
+                }
+            }
+        }
+        histogram
+    }
+
+    fn add(&mut self, pixel: [f64; 3], count: usize) {
+        if count == 0 {
+            return;
+        }
+        let idx = pixel.iter().fold(0, |idx, channel| {
+            idx << BITS | (*channel as usize) >> (8 - BITS)
+        });
+        let (total, sum) = &mut self.bins[idx];
+        *total += count;
+        for (sum, channel) in sum.iter_mut().zip(pixel) {
+            *sum += channel * count as f64;
+        }
+    }
+
+    // the mean color and pixel count of every used bin
+    pub fn colors(&self) -> Vec<([f64; 3], usize)> {
+        self.bins
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, sum)| (sum.map(|channel| channel / *count as f64), *count))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    pub color: [f64; 3],
+    pub count: usize,
+}
+
+impl Cluster {
+    pub fn rgb(&self) -> [u8; 3] {
+        self.color.map(|channel| channel.round() as u8)
+    }
+
+    pub fn luminance(&self) -> f64 {
+        let [r, g, b] = self.color;
+        0.299 * r + 0.587 * g + 0.114 * b
+    }
+
+    pub fn distance(&self, other: &Cluster) -> f64 {
+        let [red, green, blue] = self.color;
+        let [r, g, b] = other.color;
+        color_distance(red, green, blue, r, g, b)
+    }
+}
+
+// weighted k-means, seeded with the most common color and then always the color farthest from all seeds
+pub fn kmeans(colors: &[([f64; 3], usize)], k: usize) -> Vec<Cluster> {
+    let distance = |a: [f64; 3], b: [f64; 3]| color_distance(a[0], a[1], a[2], b[0], b[1], b[2]);
+    let mut centers: Vec<[f64; 3]> = Vec::new();
+    if let Some((color, _)) = colors.iter().max_by_key(|(_, count)| *count) {
+        centers.push(*color);
+    }
+    while centers.len() < k {
+        let farthest = colors
+            .iter()
+            .map(|(color, count)| {
+                let nearest = centers
+                    .iter()
+                    .map(|center| distance(*center, *color))
+                    .fold(f64::MAX, f64::min);
+                (color, nearest * nearest * *count as f64)
+            })
+            .filter(|(_, weight)| *weight > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match farthest {
+            Some((color, _)) => centers.push(*color),
+            None => break,
+        }
+    }
+
+    let mut clusters = Vec::new();
+    for _ in 0..ITERATIONS {
+        let mut sums = vec![(0, [0.0; 3]); centers.len()];
+        for (color, count) in colors {
+            let (nearest, _) = centers
+                .iter()
+                .enumerate()
+                .map(|(idx, center)| (idx, distance(*center, *color)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            sums[nearest].0 += count;
+            for (sum, channel) in sums[nearest].1.iter_mut().zip(color) {
+                *sum += channel * *count as f64;
+            }
+        }
+        clusters = sums
+            .into_iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, sum)| Cluster {
+                color: sum.map(|channel| channel / count as f64),
+                count,
+            })
+            .collect::<Vec<Cluster>>();
+        let moved = clusters.len() != centers.len()
+            || clusters
+                .iter()
+                .zip(&centers)
+                .any(|(cluster, center)| distance(cluster.color, *center) > 1e-6);
+        centers = clusters.iter().map(|cluster| cluster.color).collect();
+        if !moved {
+            break;
+        }
+    }
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.count));
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kmeans_finds_paper_ink_and_highlighter() {
+        let mut pixels = Vec::new();
+        pixels.extend([[250.0, 248.0, 240.0]; 700]);
+        pixels.extend([[245.0, 250.0, 236.0]; 100]);
+        pixels.extend([[20.0, 20.0, 25.0]; 150]);
+        pixels.extend([[254.0, 218.0, 13.0]; 50]);
+        let histogram = ColorHistogram::new(Pixels::Rgb(&mut pixels.into_iter()));
+        let clusters = kmeans(&histogram.colors(), 3);
+        assert_eq!(clusters.len(), 3);
+        assert_eq!(clusters[0].count, 800);
+        assert_eq!(clusters[1].rgb(), [20, 20, 25]);
+        assert_eq!(clusters[2].rgb(), [254, 218, 13]);
+    }
+}
@@ -0,0 +1,303 @@
+use crate::decoder::color_distance;
+use clap::ValueEnum;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+// the delta e that counts as distance 1, about the difference between black and white
+const MAX_DELTA_E: f64 = 100.0;
+const WHITE: [f64; 3] = [0.95047, 1.0, 1.08883];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Metric {
+    /// Euclidean distance of the rgb values
+    Rgb,
+    /// Euclidean distance in CIELAB (delta e / 100)
+    Cie76,
+    /// CIE94 delta e (graphic arts weights) / 100
+    Cie94,
+    /// CIEDE2000 delta e / 100
+    Ciede2000,
+    /// Largest of the hue, saturation and value differences, each divided by its --hsv-range
+    Hsv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Add the color to the black pixels
+    Merge,
+    /// Remove the color from the black pixels
+    Erase,
+    /// Remove the color from the black pixels and output it as a page of its own
+    KeepSeparate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtraColor {
+    pub rgb: [u8; 3],
+    pub threshold: f64,
+    pub mode: ColorMode,
+}
+
+impl FromStr for ExtraColor {
+    type Err = String;
+
+    // r,g,b:threshold[:mode]
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.split(':');
+        let rgb = parts
+            .next()
+            .unwrap_or_default()
+            .split(',')
+            .map(|channel| channel.trim().parse::<u8>().map_err(|err| err.to_string()))
+            .collect::<Result<Vec<u8>, String>>()?
+            .try_into()
+            .map_err(|_| format!("expected three comma separated channels, got {value}"))?;
+        let threshold = parts
+            .next()
+            .ok_or(format!("expected r,g,b:threshold[:mode], got {value}"))?
+            .trim()
+            .parse::<f64>()
+            .map_err(|err| err.to_string())?;
+        let mode = match parts.next() {
+            Some(mode) => ColorMode::from_str(mode.trim(), true)?,
+            None => ColorMode::Merge,
+        };
+        if parts.next().is_some() {
+            return Err(format!("expected r,g,b:threshold[:mode], got {value}"));
+        }
+        Ok(Self {
+            rgb,
+            threshold,
+            mode,
+        })
+    }
+}
+
+pub struct ColorMatcher {
+    metric: Metric,
+    target: [f64; 3],
+    converted: [f64; 3],
+    ranges: [f64; 3],
+}
+
+impl ColorMatcher {
+    pub fn new(metric: Metric, target: [f64; 3], ranges: [f64; 3]) -> Self {
+        let converted = match metric {
+            Metric::Rgb => target,
+            Metric::Hsv => hsv(target),
+            Metric::Cie76 | Metric::Cie94 | Metric::Ciede2000 => lab(target),
+        };
+        Self {
+            metric,
+            target,
+            converted,
+            ranges,
+        }
+    }
+
+    pub fn distance(&self, pixel: [f64; 3]) -> f64 {
+        match self.metric {
+            Metric::Rgb => {
+                let [red, green, blue] = self.target;
+                let [r, g, b] = pixel;
+                color_distance(red, green, blue, r, g, b)
+            }
+            Metric::Cie76 => cie76(self.converted, lab(pixel)) / MAX_DELTA_E,
+            Metric::Cie94 => cie94(self.converted, lab(pixel)) / MAX_DELTA_E,
+            Metric::Ciede2000 => ciede2000(self.converted, lab(pixel)) / MAX_DELTA_E,
+            Metric::Hsv => {
+                let [h0, s0, v0] = self.converted;
+                let [h1, s1, v1] = hsv(pixel);
+                // the hue of grays is meaningless
+                let hue = if s0 == 0.0 || s1 == 0.0 {
+                    0.0
+                } else {
+                    let hue = (h0 - h1).abs();
+                    hue.min(360.0 - hue)
+                };
+                let [hr, sr, vr] = self.ranges;
+                (hue / hr)
+                    .max((s0 - s1).abs() / sr)
+                    .max((v0 - v1).abs() / vr)
+            }
+        }
+    }
+}
+
+fn linear(channel: f64) -> f64 {
+    let channel = channel / 255.0;
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn lab_f(t: f64) -> f64 {
+    if t > 216.0 / 24389.0 {
+        t.cbrt()
+    } else {
+        (24389.0 / 27.0 * t + 16.0) / 116.0
+    }
+}
+
+pub fn lab(rgb: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(linear);
+    let x = 0.4124564 * r + 0.3575761 * g + 0.1804375 * b;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = 0.0193339 * r + 0.119192 * g + 0.9503041 * b;
+    let fx = lab_f(x / WHITE[0]);
+    let fy = lab_f(y / WHITE[1]);
+    let fz = lab_f(z / WHITE[2]);
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+// hue in degrees, saturation and value between 0 and 1
+pub fn hsv(rgb: [f64; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(|channel| channel / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    [hue, saturation, max]
+}
+
+pub fn cie76(lab0: [f64; 3], lab1: [f64; 3]) -> f64 {
+    ((lab0[0] - lab1[0]).powi(2) + (lab0[1] - lab1[1]).powi(2) + (lab0[2] - lab1[2]).powi(2)).sqrt()
+}
+
+pub fn cie94(lab0: [f64; 3], lab1: [f64; 3]) -> f64 {
+    let delta_l = lab0[0] - lab1[0];
+    let c0 = lab0[1].hypot(lab0[2]);
+    let c1 = lab1[1].hypot(lab1[2]);
+    let delta_c = c0 - c1;
+    let delta_h_squared =
+        ((lab0[1] - lab1[1]).powi(2) + (lab0[2] - lab1[2]).powi(2) - delta_c * delta_c).max(0.0);
+    let sc = 1.0 + 0.045 * c0;
+    let sh = 1.0 + 0.015 * c0;
+    (delta_l * delta_l + (delta_c / sc).powi(2) + delta_h_squared / (sh * sh)).sqrt()
+}
+
+pub fn ciede2000(lab0: [f64; 3], lab1: [f64; 3]) -> f64 {
+    let [l0, a0, b0] = lab0;
+    let [l1, a1, b1] = lab1;
+    let c_mean = (a0.hypot(b0) + a1.hypot(b1)) / 2.0;
+    let g = 0.5 * (1.0 - (c_mean.powi(7) / (c_mean.powi(7) + 25f64.powi(7))).sqrt());
+    let a0 = a0 * (1.0 + g);
+    let a1 = a1 * (1.0 + g);
+    let c0 = a0.hypot(b0);
+    let c1 = a1.hypot(b1);
+    let hue = |a: f64, b: f64| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let h0 = hue(a0, b0);
+    let h1 = hue(a1, b1);
+
+    let delta_l = l1 - l0;
+    let delta_c = c1 - c0;
+    let delta_h = if c0 * c1 == 0.0 {
+        0.0
+    } else if (h1 - h0).abs() <= 180.0 {
+        h1 - h0
+    } else if h1 - h0 > 180.0 {
+        h1 - h0 - 360.0
+    } else {
+        h1 - h0 + 360.0
+    };
+    let delta_h = 2.0 * (c0 * c1).sqrt() * (delta_h.to_radians() / 2.0).sin();
+
+    let l_mean = (l0 + l1) / 2.0;
+    let c_mean = (c0 + c1) / 2.0;
+    let h_mean = if c0 * c1 == 0.0 {
+        h0 + h1
+    } else if (h0 - h1).abs() <= 180.0 {
+        (h0 + h1) / 2.0
+    } else if h0 + h1 < 360.0 {
+        (h0 + h1 + 360.0) / 2.0
+    } else {
+        (h0 + h1 - 360.0) / 2.0
+    };
+    let t = 1.0 - 0.17 * (h_mean - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_mean).to_radians().cos()
+        + 0.32 * (3.0 * h_mean + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_mean - 63.0).to_radians().cos();
+    let delta_theta = 30.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp();
+    let rc = 2.0 * (c_mean.powi(7) / (c_mean.powi(7) + 25f64.powi(7))).sqrt();
+    let sl = 1.0 + 0.015 * (l_mean - 50.0).powi(2) / (20.0 + (l_mean - 50.0).powi(2)).sqrt();
+    let sc = 1.0 + 0.045 * c_mean;
+    let sh = 1.0 + 0.015 * c_mean * t;
+    let rt = -(2.0 * delta_theta * PI / 180.0).sin() * rc;
+
+    ((delta_l / sl).powi(2)
+        + (delta_c / sc).powi(2)
+        + (delta_h / sh).powi(2)
+        + rt * (delta_c / sc) * (delta_h / sh))
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ciede2000_reference_pairs() {
+        // from the test data of Sharma, Wu and Dalal
+        for (lab0, lab1, expected) in [
+            ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+            ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+            ([50.0, -1.0, 2.0], [50.0, 0.0, 0.0], 2.3669),
+            ([2.0776, 0.0795, -1.135], [0.9033, -0.0636, -0.5514], 0.9082),
+        ] {
+            assert!((ciede2000(lab0, lab1) - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn lab_of_white_and_black() {
+        let [l, a, b] = lab([255.0; 3]);
+        assert!((l - 100.0).abs() < 1e-3 && a.abs() < 1e-3 && b.abs() < 1e-3);
+        assert_eq!(lab([0.0; 3])[0], 0.0);
+    }
+
+    #[test]
+    fn parse_extra_color() {
+        assert_eq!(
+            "254,218,13:0.2".parse(),
+            Ok(ExtraColor {
+                rgb: [254, 218, 13],
+                threshold: 0.2,
+                mode: ColorMode::Merge,
+            })
+        );
+        assert_eq!(
+            "200,0,0:0.3:keep-separate"
+                .parse::<ExtraColor>()
+                .unwrap()
+                .mode,
+            ColorMode::KeepSeparate
+        );
+        assert!("200,0:0.3".parse::<ExtraColor>().is_err());
+        assert!("200,0,0:0.3:paint".parse::<ExtraColor>().is_err());
+    }
+
+    #[test]
+    fn hsv_range() {
+        let matcher = ColorMatcher::new(Metric::Hsv, [254.0, 218.0, 13.0], [30.0, 0.5, 0.5]);
+        assert!(matcher.distance([240.0, 230.0, 60.0]) < 1.0);
+        assert!(matcher.distance([245.0, 243.0, 235.0]) > 1.0);
+        assert!(matcher.distance([60.0, 120.0, 250.0]) > 1.0);
+    }
+}
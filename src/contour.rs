@@ -0,0 +1,450 @@
+use crate::image::Image;
+use crate::label::{Connectivity, Labels};
+use crate::object::Object;
+
+const EAST: u8 = 1;
+const SOUTH: u8 = 2;
+const WEST: u8 = 4;
+const NORTH: u8 = 8;
+
+// the freeman chain code directions, counterclockwise from east with y growing downwards
+const FREEMAN: [(isize, isize); 8] = [
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contour {
+    points: Vec<(f64, f64)>,
+}
+
+impl Contour {
+    pub fn new(points: Vec<(f64, f64)>) -> Self {
+        Self { points }
+    }
+
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    pub fn area(&self) -> f64 {
+        let n = self.points.len();
+        (0..n)
+            .map(|i| {
+                let (x0, y0) = self.points[i];
+                let (x1, y1) = self.points[(i + 1) % n];
+                x0 * y1 - x1 * y0
+            })
+            .sum::<f64>()
+            / 2.0
+    }
+
+    pub fn is_hole(&self) -> bool {
+        self.area() < 0.0
+    }
+
+    pub fn offset(self, offx: f64, offy: f64) -> Self {
+        let points = self
+            .points
+            .into_iter()
+            .map(|(x, y)| (x + offx, y + offy))
+            .collect();
+        Self { points }
+    }
+
+    pub fn simplify(&self, epsilon: f64) -> Self {
+        if self.points.len() < 4 || epsilon <= 0.0 {
+            return self.clone();
+        }
+        let first = self.points[0];
+        let (split, _) = self
+            .points
+            .iter()
+            .enumerate()
+            .map(|(idx, point)| (idx, distance(first, *point)))
+            .fold(
+                (0, 0.0),
+                |max, current| {
+                    if current.1 > max.1 {
+                        current
+                    } else {
+                        max
+                    }
+                },
+            );
+        let mut closed = self.points.clone();
+        closed.push(first);
+
+        let mut points = douglas_peucker(&closed[..=split], epsilon);
+        points.pop();
+        points.extend(douglas_peucker(&closed[split..], epsilon));
+        points.pop();
+        Self { points }
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1)).sqrt()
+}
+
+fn line_distance(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let length = distance(start, end);
+    if length == 0.0 {
+        return distance(point, start);
+    }
+    ((end.0 - start.0) * (start.1 - point.1) - (start.0 - point.0) * (end.1 - start.1)).abs()
+        / length
+}
+
+fn douglas_peucker(points: &[(f64, f64)], epsilon: f64) -> Vec<(f64, f64)> {
+    let start = points[0];
+    let end = points[points.len() - 1];
+    let (idx, max) = points
+        .iter()
+        .enumerate()
+        .skip(1)
+        .take(points.len().saturating_sub(2))
+        .map(|(idx, point)| (idx, line_distance(*point, start, end)))
+        .fold(
+            (0, 0.0),
+            |max, current| {
+                if current.1 > max.1 {
+                    current
+                } else {
+                    max
+                }
+            },
+        );
+    if max > epsilon {
+        let mut result = douglas_peucker(&points[..=idx], epsilon);
+        result.pop();
+        result.extend(douglas_peucker(&points[idx..], epsilon));
+        result
+    } else {
+        vec![start, end]
+    }
+}
+
+pub fn trace<O>(object: O, connectivity: Connectivity) -> Vec<Contour>
+where
+    O: Object,
+{
+    let offx = object.xmin() as f64;
+    let offy = object.ymin() as f64;
+    trace_image(&object.to_image(), connectivity)
+        .into_iter()
+        .map(|contour| contour.offset(offx, offy))
+        .collect()
+}
+
+pub fn trace_image(image: &Image, connectivity: Connectivity) -> Vec<Contour> {
+    let width = image.width();
+    let height = image.height();
+    let vertex = |x: usize, y: usize| y * (width + 1) + x;
+    let black = |x: usize, y: usize| x < width && y < height && image.get(x, y);
+
+    // the pixel edges between black and white, walked with the black pixel on the right
+    let mut edges = vec![0u8; (width + 1) * (height + 1)];
+    for y in 0..height {
+        for x in 0..width {
+            if !image.get(x, y) {
+                continue;
+            }
+            if y == 0 || !black(x, y - 1) {
+                edges[vertex(x, y)] |= EAST;
+            }
+            if !black(x + 1, y) {
+                edges[vertex(x + 1, y)] |= SOUTH;
+            }
+            if !black(x, y + 1) {
+                edges[vertex(x + 1, y + 1)] |= WEST;
+            }
+            if x == 0 || !black(x - 1, y) {
+                edges[vertex(x, y + 1)] |= NORTH;
+            }
+        }
+    }
+
+    let mut contours = Vec::new();
+    for start in 0..edges.len() {
+        while edges[start] != 0 {
+            let (mut x, mut y) = (start % (width + 1), start / (width + 1));
+            let mut direction = [EAST, SOUTH, WEST, NORTH]
+                .into_iter()
+                .find(|direction| edges[start] & direction != 0)
+                .unwrap();
+            let mut points = vec![(x as f64, y as f64)];
+            loop {
+                edges[vertex(x, y)] &= !direction;
+                match direction {
+                    EAST => x += 1,
+                    SOUTH => y += 1,
+                    WEST => x -= 1,
+                    _ => y -= 1,
+                }
+                let outgoing = edges[vertex(x, y)];
+                if outgoing == 0 {
+                    break;
+                }
+                // turning right first keeps diagonal pixels separate, turning left first joins them
+                let right = (direction << 1 | direction >> 3 & 1) & 15;
+                let left = (direction >> 1 | (direction & 1) << 3) & 15;
+                let turns = match connectivity {
+                    Connectivity::Four => [right, direction, left],
+                    Connectivity::Eight => [left, direction, right],
+                };
+                let next = turns.into_iter().find(|next| outgoing & next != 0).unwrap();
+                if next != direction {
+                    points.push((x as f64, y as f64));
+                }
+                direction = next;
+            }
+            contours.push(Contour::new(points));
+        }
+    }
+    contours
+}
+
+// a closed chain of boundary pixels as freeman chain codes from its start pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Boundary {
+    start: (usize, usize),
+    codes: Vec<u8>,
+    hole: bool,
+}
+
+impl Boundary {
+    pub fn new(start: (usize, usize), codes: Vec<u8>, hole: bool) -> Self {
+        Self { start, codes, hole }
+    }
+
+    pub fn start(&self) -> (usize, usize) {
+        self.start
+    }
+
+    pub fn codes(&self) -> &[u8] {
+        &self.codes
+    }
+
+    pub fn is_hole(&self) -> bool {
+        self.hole
+    }
+
+    // the pixels in the order of the chain, without the start at the end again
+    pub fn points(&self) -> Vec<(usize, usize)> {
+        let mut point = self.start;
+        let mut points = vec![point];
+        for code in &self.codes[..self.codes.len().saturating_sub(1)] {
+            let (dx, dy) = FREEMAN[*code as usize];
+            point = (
+                point.0.wrapping_add_signed(dx),
+                point.1.wrapping_add_signed(dy),
+            );
+            points.push(point);
+        }
+        points
+    }
+
+    // the length of the chain through the pixel centers, straight steps count 1 and diagonal
+    // steps the square root of 2, shorter than the edge count of an object around its pixels
+    pub fn perimeter(&self) -> f64 {
+        self.codes
+            .iter()
+            .map(|code| if code % 2 == 0 { 1.0 } else { 2f64.sqrt() })
+            .sum()
+    }
+
+    // the area of the polygon through the pixel centers, negative for holes
+    pub fn area(&self) -> f64 {
+        let points = self.points();
+        let n = points.len();
+        (0..n)
+            .map(|i| {
+                let (x0, y0) = (points[i].0 as f64, points[i].1 as f64);
+                let (x1, y1) = (points[(i + 1) % n].0 as f64, points[(i + 1) % n].1 as f64);
+                x0 * y1 - x1 * y0
+            })
+            .sum::<f64>()
+            / 2.0
+    }
+
+    pub fn offset(self, offx: usize, offy: usize) -> Self {
+        Self {
+            start: (self.start.0 + offx, self.start.1 + offy),
+            ..self
+        }
+    }
+}
+
+// moore neighbor tracing of the outer boundary of the first object and the inner boundaries of its holes,
+// outer boundaries run clockwise and inner ones counterclockwise
+pub fn boundaries(image: &Image, connectivity: Connectivity) -> Vec<Boundary> {
+    let (width, height) = (image.width(), image.height());
+    let Some(start) = (0..height).find_map(|y| image.find(0, y, width, true).map(|x| (x, y)))
+    else {
+        return Vec::new();
+    };
+    // the other objects would add their holes, or split the holes of the first one
+    let labels = Labels::new(image, true, connectivity);
+    let bits = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)));
+    let image = &Image::from_bits(
+        width,
+        height,
+        bits.map(|(x, y)| labels.label(x, y) == Some(0)),
+    );
+    // coming from below, the search starts at the white pixel to the west
+    let mut boundaries = vec![Boundary::new(
+        start,
+        follow(image, start, 2, connectivity),
+        false,
+    )];
+
+    let holes = Labels::new(image, false, connectivity.opposite());
+    for (idx, hole) in holes.components().iter().enumerate() {
+        if hole.touches_border(width, height) {
+            continue;
+        }
+        // the pixel west of the first pixel of a hole is black, coming from above the search starts at the hole
+        let x = (hole.xmin..=hole.xmax)
+            .find(|x| holes.label(*x, hole.ymin) == Some(idx))
+            .unwrap();
+        let start = (x - 1, hole.ymin);
+        boundaries.push(Boundary::new(
+            start,
+            follow(image, start, 6, connectivity),
+            true,
+        ));
+    }
+    boundaries
+}
+
+// walks the boundary with the white side on the left, trying the leftmost neighbor first,
+// until it leaves the start in the same direction again (jacob's stopping criterion)
+fn follow(
+    image: &Image,
+    start: (usize, usize),
+    mut direction: usize,
+    connectivity: Connectivity,
+) -> Vec<u8> {
+    let black = |x: usize, y: usize, code: usize| {
+        let (dx, dy) = FREEMAN[code];
+        let (x, y) = (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy));
+        image.get(x, y).then_some((x, y))
+    };
+    let (mut x, mut y) = start;
+    let mut codes: Vec<u8> = Vec::new();
+    loop {
+        let turns: &[usize] = match (connectivity, direction % 2) {
+            (Connectivity::Four, _) => &[2, 0, 6, 4],
+            (Connectivity::Eight, 0) => &[2, 1, 0, 7, 6, 5, 4, 3],
+            (Connectivity::Eight, _) => &[3, 2, 1, 0, 7, 6, 5, 4],
+        };
+        let Some((code, next)) = turns.iter().find_map(|turn| {
+            let code = (direction + turn) % 8;
+            black(x, y, code).map(|next| (code, next))
+        }) else {
+            break;
+        };
+        if (x, y) == start && codes.first() == Some(&(code as u8)) {
+            break;
+        }
+        codes.push(code as u8);
+        (x, y) = next;
+        direction = code;
+    }
+    codes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_ring() {
+        let mut image = Image::new_empty(3, 3);
+        for (x, y) in [
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (0, 1),
+            (2, 1),
+            (0, 2),
+            (1, 2),
+            (2, 2),
+        ] {
+            image.set(x, y, true);
+        }
+        let contours = trace_image(&image, Connectivity::Four);
+        assert_eq!(contours.len(), 2);
+        assert_eq!(contours[0].area(), 9.0);
+        assert_eq!(contours[1].area(), -1.0);
+        assert!(contours[1].is_hole());
+    }
+
+    #[test]
+    fn trace_diagonal_pixels() {
+        let image = Image::from_rows(&["#.", ".#"]);
+        assert_eq!(trace_image(&image, Connectivity::Four).len(), 2);
+        let contours = trace_image(&image, Connectivity::Eight);
+        assert_eq!(contours.len(), 1);
+        assert_eq!(contours[0].area(), 2.0);
+    }
+
+    #[test]
+    fn boundaries_of_ring() {
+        let image = Image::from_rows(&["###", "#.#", "###"]);
+        let boundaries = boundaries(&image, Connectivity::Eight);
+        assert_eq!(boundaries.len(), 2);
+        assert_eq!(boundaries[0].codes(), &[0, 0, 6, 6, 4, 4, 2, 2]);
+        assert_eq!(boundaries[0].perimeter(), 8.0);
+        assert_eq!(boundaries[0].area(), 4.0);
+        assert!(boundaries[1].is_hole());
+        assert_eq!(boundaries[1].codes(), &[7, 1, 3, 5]);
+        assert_eq!(boundaries[1].area(), -2.0);
+
+        let boundaries = super::boundaries(&image, Connectivity::Four);
+        assert_eq!(boundaries[1].points().len(), 8);
+        assert_eq!(boundaries[1].area(), -4.0);
+    }
+
+    #[test]
+    fn simplify_staircase() {
+        let contour = Contour::new(vec![
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 1.0),
+            (2.0, 1.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+        ]);
+        assert_eq!(contour.simplify(0.8).points().len(), 3);
+    }
+
+    #[test]
+    fn boundaries_of_the_first_object() {
+        // a ring with a speck in its hole next to another ring
+        let image = Image::from_rows(&[
+            "#####.###",
+            "#...#.#.#",
+            "#.#.#.###",
+            "#...#....",
+            "#####....",
+        ]);
+        for connectivity in [Connectivity::Four, Connectivity::Eight] {
+            let boundaries = boundaries(&image, connectivity);
+            assert_eq!(boundaries.len(), 2);
+            assert!(!boundaries[0].is_hole() && boundaries[1].is_hole());
+            assert_eq!(boundaries[0].points().len(), 16);
+            // the hole without the speck is 3 by 3 pixels
+            let points = boundaries[1].points();
+            assert!(points.iter().all(|(x, y)| *x <= 4 && *y <= 4));
+            assert!(!points.contains(&(2, 2)));
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Luminance {
+    /// ITU-R BT.601 weights (0.299, 0.587, 0.114), the same as Pillow's 'L' mode
+    Rec601,
+    /// ITU-R BT.709 weights (0.2126, 0.7152, 0.0722)
+    Rec709,
+    /// The unweighted mean of the three channels
+    Average,
+    /// Only the red channel
+    Red,
+    /// Only the green channel
+    Green,
+    /// Only the blue channel
+    Blue,
+}
+
+impl Luminance {
+    pub fn apply(&self, r: f64, g: f64, b: f64) -> f64 {
+        match self {
+            Self::Rec601 => 0.299 * r + 0.587 * g + 0.114 * b,
+            Self::Rec709 => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+            Self::Average => (r + g + b) / 3.0,
+            Self::Red => r,
+            Self::Green => g,
+            Self::Blue => b,
+        }
+    }
+
+    pub fn level(&self, r: f64, g: f64, b: f64) -> u8 {
+        self.apply(r, g, b).round().clamp(0.0, 255.0) as u8
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Quantizer {
+    palette: Vec<u8>,
+    lookup: [u8; 256],
+}
+
+impl Quantizer {
+    pub fn new(histogram: &[usize; 256], colors: usize) -> Self {
+        let colors = colors.clamp(1, 256);
+        let mut boxes = vec![(0usize, 255usize)];
+
+        while boxes.len() < colors {
+            let candidate = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, (min, max))| Self::used_levels(histogram, *min, *max) > 1)
+                .max_by_key(|(_, (min, max))| Self::count(histogram, *min, *max) * (max - min + 1))
+                .map(|(idx, _)| idx);
+            let Some(idx) = candidate else {
+                break;
+            };
+            let (min, max) = boxes[idx];
+            let split = Self::median(histogram, min, max);
+            boxes[idx] = (min, split);
+            boxes.push((split + 1, max));
+        }
+        boxes.sort();
+
+        let mut palette = Vec::with_capacity(boxes.len());
+        let mut lookup = [0; 256];
+        for (idx, (min, max)) in boxes.into_iter().enumerate() {
+            let count = Self::count(histogram, min, max);
+            let level = ((min..=max).map(|l| l * histogram[l]).sum::<usize>() + count / 2)
+                .checked_div(count)
+                .unwrap_or((min + max) / 2);
+            palette.push(level as u8);
+            for entry in lookup.iter_mut().take(max + 1).skip(min) {
+                *entry = idx as u8;
+            }
+        }
+
+        Self { palette, lookup }
+    }
+
+    pub fn from_levels<T>(levels: T, colors: usize) -> Self
+    where
+        T: IntoIterator<Item = u8>,
+    {
+        let mut histogram = [0; 256];
+        for level in levels {
+            histogram[level as usize] += 1;
+        }
+        Self::new(&histogram, colors)
+    }
+
+    pub fn palette(&self) -> &[u8] {
+        &self.palette
+    }
+
+    pub fn rgb_palette(&self) -> Vec<u8> {
+        self.palette.iter().flat_map(|level| [*level; 3]).collect()
+    }
+
+    pub fn index(&self, level: u8) -> u8 {
+        self.lookup[level as usize]
+    }
+
+    fn count(histogram: &[usize; 256], min: usize, max: usize) -> usize {
+        histogram[min..=max].iter().sum()
+    }
+
+    fn used_levels(histogram: &[usize; 256], min: usize, max: usize) -> usize {
+        histogram[min..=max]
+            .iter()
+            .filter(|count| **count > 0)
+            .count()
+    }
+
+    fn median(histogram: &[usize; 256], min: usize, max: usize) -> usize {
+        let first = (min..=max).find(|l| histogram[*l] > 0).unwrap_or(min);
+        let last = (min..=max).rev().find(|l| histogram[*l] > 0).unwrap_or(max);
+        let half = Self::count(histogram, min, max) / 2;
+        let mut sum = 0;
+        for (level, count) in histogram.iter().enumerate().take(last).skip(first) {
+            sum += count;
+            if sum >= half {
+                return level;
+            }
+        }
+        last - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantizer_keeps_few_levels() {
+        let quantizer = Quantizer::from_levels([0, 0, 128, 255], 256);
+        assert_eq!(quantizer.palette(), &[0, 128, 255]);
+        assert_eq!(quantizer.index(128), 1);
+    }
+
+    #[test]
+    fn quantizer_merges_levels() {
+        let quantizer = Quantizer::from_levels([0, 10, 240, 250], 2);
+        assert_eq!(quantizer.palette(), &[5, 245]);
+        assert_eq!(quantizer.index(10), 0);
+        assert_eq!(quantizer.index(240), 1);
+    }
+}
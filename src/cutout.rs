@@ -1,5 +1,6 @@
 use crate::image::Image;
-use crate::object::{IntoPixelsToOneHeightRectangles, Pixel, RectangleCollection};
+use crate::label::{Connectivity, Labels};
+use crate::object::{IntoPixelsToOneHeightRectangles, Object, Pixel, RectangleCollection};
 
 #[derive(Clone, Debug)]
 pub struct Cutout<'a> {
@@ -56,14 +57,14 @@ impl<'a> Cutout<'a> {
     }
 
     pub fn is_blank(&self) -> bool {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                if self.get(x, y) {
-                    return false;
-                }
-            }
-        }
-        return true;
+        self.image
+            .is_blank_area(self.offx, self.offy, self.width, self.height)
+    }
+
+    pub fn find(&self, x: usize, y: usize, value: bool) -> Option<usize> {
+        self.image
+            .find(x + self.offx, y + self.offy, self.offx + self.width, value)
+            .map(|x| x - self.offx)
     }
 
     fn trimm_y(&self, reverse: bool) -> Option<Self> {
@@ -138,61 +139,46 @@ impl<'a> Cutout<'a> {
         None
     }
 
-    pub fn columns(&self, reverse: bool) -> CutoutColumnIterator {
+    pub fn columns(&self, reverse: bool) -> CutoutColumnIterator<'_> {
         CutoutColumnIterator::new(self, reverse)
     }
 
-    pub fn lines(&self, reverse: bool) -> CutoutLineIterator {
+    pub fn lines(&self, reverse: bool) -> CutoutLineIterator<'_> {
         CutoutLineIterator::new(self, reverse)
     }
 
-    pub fn yparts(&self) -> CutoutYPartIterator {
+    pub fn yparts(&self) -> CutoutYPartIterator<'_> {
         CutoutYPartIterator::new(self)
     }
 
-    pub fn pixels(&self, invert: bool, image_scope: bool) -> CutoutPixelIterator {
+    pub fn pixels(&self, invert: bool, image_scope: bool) -> CutoutPixelIterator<'_> {
         CutoutPixelIterator::new(self, invert, image_scope)
     }
 
-    pub fn objects(&self, invert: bool) -> Vec<RectangleCollection> {
-        let mut collections: Vec<RectangleCollection> = Vec::new();
+    // the runs of each connected component, in the order of its first pixel
+    pub fn objects(&self, invert: bool, connectivity: Connectivity) -> Vec<RectangleCollection> {
+        let labels = Labels::new(&self.to_image(), !invert, connectivity);
+        let mut collections: Vec<Option<RectangleCollection>> =
+            vec![None; labels.components().len()];
         for line in self.lines(false) {
             for rectangle in line.pixels(invert, true).to_one_height_rectangles() {
-                let touched_collection_ids = collections
-                    .iter()
-                    .enumerate()
-                    .map(|tupel| (tupel.0, tupel.1.bottom_touch(&rectangle)))
-                    .filter(|tupel| tupel.1)
-                    .map(|tupel| tupel.0)
-                    .collect::<Vec<usize>>();
-                if touched_collection_ids.len() == 0 {
-                    collections.push(RectangleCollection::new(rectangle));
-                } else if touched_collection_ids.len() == 1 {
-                    collections[touched_collection_ids[0]].add_rectangle(rectangle);
-                } else {
-                    let mut collection = RectangleCollection::new(rectangle);
-                    for id in touched_collection_ids.into_iter().rev() {
-                        for rectangle in collections.swap_remove(id).rectangles() {
-                            collection.add_rectangle(rectangle)
-                        }
-                    }
-                    collections.push(collection);
+                let Some(label) =
+                    labels.label(rectangle.xmin() - self.offx, rectangle.ymin() - self.offy)
+                else {
+                    continue;
+                };
+                match &mut collections[label] {
+                    Some(collection) => collection.add_rectangle(rectangle),
+                    collection => *collection = Some(RectangleCollection::new(rectangle)),
                 }
             }
         }
-        return collections;
+        collections.into_iter().flatten().collect()
     }
 
     pub fn to_image(&self) -> Image {
-        let width = self.width;
-        let height = self.height;
-        let mut image = Image::new_empty(width, height);
-        for y in 0..height {
-            for x in 0..width {
-                image.set(x, y, self.get(x, y))
-            }
-        }
-        image
+        self.image
+            .crop(self.offx, self.offy, self.width, self.height)
     }
 
     pub fn neighbors(&self, x: usize, y: usize) -> usize {
@@ -209,7 +195,7 @@ impl<'a> Cutout<'a> {
         if self.get(x, y + 1) {
             result += 1;
         }
-        return result;
+        result
     }
 
     pub fn left_border(&self, pixels: usize) -> Self {
@@ -269,19 +255,17 @@ impl<'a> Iterator for CutoutPixelIterator<'a> {
             } else {
                 self.currx + 1
             };
-            for x in xmax..(self.cutout.width) {
+            if let Some(x) = self.cutout.find(xmax, y, !self.invert) {
                 self.xisminusone = false;
-                if self.cutout.get(x, y) && !self.invert || !self.cutout.get(x, y) && self.invert {
-                    self.currx = x;
-                    self.curry = y;
-                    if self.image_scope {
-                        return Some(Pixel::new(
-                            self.currx + self.cutout.offx,
-                            self.curry + self.cutout.offy,
-                        ));
-                    } else {
-                        return Some(Pixel::new(self.currx, self.curry));
-                    }
+                self.currx = x;
+                self.curry = y;
+                if self.image_scope {
+                    return Some(Pixel::new(
+                        self.currx + self.cutout.offx,
+                        self.curry + self.cutout.offy,
+                    ));
+                } else {
+                    return Some(Pixel::new(self.currx, self.curry));
                 }
             }
             self.currx = 0;
@@ -326,7 +310,7 @@ impl<'a> Iterator for CutoutColumnIterator<'a> {
         let width = 1;
         let offy = self.cutout.offy;
         let height = self.cutout.height;
-        self.current = self.current + 1;
+        self.current += 1;
         Some(Cutout::new(image, width, height, offx, offy))
     }
 }
@@ -366,7 +350,7 @@ impl<'a> Iterator for CutoutLineIterator<'a> {
             self.cutout.offy + self.current
         };
         let height = 1;
-        self.current = self.current + 1;
+        self.current += 1;
         Some(Cutout::new(image, width, height, offx, offy))
     }
 }
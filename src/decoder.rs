@@ -1,81 +1,234 @@
-pub struct ThreeByteDecoder<'a> {
+use crate::background::Flatten;
+use crate::color::{ColorMatcher, Metric};
+use crate::convert::Luminance;
+
+const MAX_DISTANCE: f64 = 441.6729559300637;
+
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub threshold: f64,
+    pub luminance: Option<Luminance>,
+    pub colors: Option<usize>,
+    pub background: [f64; 3],
+    pub metric: Metric,
+    pub hsv_range: [f64; 3],
+    pub flatten: Option<Flatten>,
+}
+
+impl DecodeOptions {
+    pub fn new(red: f64, green: f64, blue: f64, threshold: f64) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            threshold,
+            luminance: None,
+            colors: None,
+            background: [255.0; 3],
+            metric: Metric::Rgb,
+            hsv_range: [180.0, 1.0, 1.0],
+            flatten: None,
+        }
+    }
+
+    pub fn matcher(&self) -> ColorMatcher {
+        ColorMatcher::new(
+            self.metric,
+            [self.red, self.green, self.blue],
+            self.hsv_range,
+        )
+    }
+}
+
+pub fn color_distance(red: f64, green: f64, blue: f64, r: f64, g: f64, b: f64) -> f64 {
+    ((red - r) * (red - r) + (green - g) * (green - g) + (blue - b) * (blue - b)).sqrt()
+        / MAX_DISTANCE
+}
+
+pub struct SampleDecoder<'a> {
     data: &'a [u8],
+    bit_depth: usize,
     idx: usize,
-    red: f64,
-    green: f64,
-    blue: f64,
-    threshold: f64,
-    max_value: f64,
 }
 
-impl<'a> ThreeByteDecoder<'a> {
-    pub fn new(data: &'a [u8], red: f64, green: f64, blue: f64, threshold: f64) -> Self {
+impl<'a> SampleDecoder<'a> {
+    pub fn new(data: &'a [u8], bit_depth: usize) -> Self {
         let idx = 0;
-        let max_value = 441.6729559300637;
         Self {
             data,
+            bit_depth,
             idx,
-            red,
-            green,
-            blue,
-            threshold,
-            max_value,
         }
     }
 }
 
-impl<'a> Iterator for ThreeByteDecoder<'a> {
-    type Item = bool;
+impl<'a> Iterator for SampleDecoder<'a> {
+    type Item = u16;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.idx + 3 > self.data.len() {
+        if self.idx + self.bit_depth > self.data.len() * 8 {
             return None;
         }
 
-        let r = self.data[self.idx] as f64;
-        let g = self.data[self.idx + 1] as f64;
-        let b = self.data[self.idx + 2] as f64;
-
-        self.idx += 3;
+        let byte = self.idx / 8;
+        let sample = match self.bit_depth {
+            16 => u16::from_be_bytes([self.data[byte], self.data[byte + 1]]),
+            8 => self.data[byte] as u16,
+            _ => {
+                let shift = 8 - self.bit_depth - self.idx % 8;
+                ((self.data[byte] >> shift) & ((1 << self.bit_depth) - 1)) as u16
+            }
+        };
+        self.idx += self.bit_depth;
 
-        Some(
-            ((self.red - r) * (self.red - r)
-                + (self.green - g) * (self.green - g)
-                + (self.blue - b) * (self.blue - b))
-                .sqrt()
-                / self.max_value
-                < self.threshold,
-        )
+        Some(sample)
     }
 }
 
-pub struct IndexedDecoder<'a> {
-    data: &'a [u8],
-    idx: usize,
-    color_palette: &'a [bool],
+pub fn composite(pixel: [f64; 3], alpha: f64, background: [f64; 3]) -> [f64; 3] {
+    [
+        pixel[0] * alpha + background[0] * (1.0 - alpha),
+        pixel[1] * alpha + background[1] * (1.0 - alpha),
+        pixel[2] * alpha + background[2] * (1.0 - alpha),
+    ]
 }
 
-impl<'a> IndexedDecoder<'a> {
-    pub fn new(color_palette: &'a [bool], data: &'a [u8]) -> Self {
-        let idx = 0;
+pub fn composite_palette(palette: &[u8], trns: Option<&[u8]>, background: [f64; 3]) -> Vec<u8> {
+    palette
+        .chunks(3)
+        .enumerate()
+        .flat_map(|(idx, rgb)| {
+            let alpha = trns
+                .and_then(|trns| trns.get(idx))
+                .map(|alpha| *alpha as f64 / 255.0)
+                .unwrap_or(1.0);
+            composite(
+                [rgb[0] as f64, rgb[1] as f64, rgb[2] as f64],
+                alpha,
+                background,
+            )
+            .map(|value| value.round() as u8)
+        })
+        .collect()
+}
+
+pub struct RgbDecoder<I>
+where
+    I: Iterator<Item = u16>,
+{
+    samples: I,
+    channels: usize,
+    scale: f64,
+    background: [f64; 3],
+    transparent: Option<[u16; 3]>,
+}
+
+impl<I> RgbDecoder<I>
+where
+    I: Iterator<Item = u16>,
+{
+    pub fn new(
+        samples: I,
+        channels: usize,
+        max_value: u16,
+        background: [f64; 3],
+        transparent: Option<[u16; 3]>,
+    ) -> Self {
+        let scale = 255.0 / max_value as f64;
         Self {
-            data,
-            idx,
-            color_palette,
+            samples,
+            channels,
+            scale,
+            background,
+            transparent,
         }
     }
 }
 
-impl<'a> Iterator for IndexedDecoder<'a> {
-    type Item = bool;
+impl<I> Iterator for RgbDecoder<I>
+where
+    I: Iterator<Item = u16>,
+{
+    type Item = [f64; 3];
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.idx >= self.data.len() {
-            return None;
+        let mut raw = [0; 4];
+        for value in raw.iter_mut().take(self.channels) {
+            *value = self.samples.next()?;
         }
 
-        let b = self.color_palette[self.data[self.idx] as usize];
-        self.idx += 1;
-        Some(b)
+        let color_channels = if self.channels < 3 { 1 } else { 3 };
+        let pixel = if color_channels == 1 {
+            [raw[0] as f64 * self.scale; 3]
+        } else {
+            [
+                raw[0] as f64 * self.scale,
+                raw[1] as f64 * self.scale,
+                raw[2] as f64 * self.scale,
+            ]
+        };
+
+        let alpha = if self.channels == color_channels + 1 {
+            raw[color_channels] as f64 * self.scale / 255.0
+        } else if self
+            .transparent
+            .is_some_and(|key| key[..color_channels] == raw[..color_channels])
+        {
+            0.0
+        } else {
+            return Some(pixel);
+        };
+
+        Some(composite(pixel, alpha, self.background))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_decoder_sub_byte() {
+        let data = [0b1011_0001];
+        assert_eq!(
+            SampleDecoder::new(&data, 2).collect::<Vec<u16>>(),
+            vec![2, 3, 0, 1]
+        );
+        assert_eq!(
+            SampleDecoder::new(&data, 1).take(3).collect::<Vec<u16>>(),
+            vec![1, 0, 1]
+        );
+    }
+
+    #[test]
+    fn sample_decoder_sixteen_bit() {
+        let data = [0x12, 0x34, 0xff, 0xff];
+        assert_eq!(
+            SampleDecoder::new(&data, 16).collect::<Vec<u16>>(),
+            vec![0x1234, 0xffff]
+        );
+    }
+
+    #[test]
+    fn rgb_decoder_composites_alpha() {
+        let samples = vec![0, 0, 0, 0, 0, 0, 0, 255, 0, 0, 0, 51];
+        assert_eq!(
+            RgbDecoder::new(samples.into_iter(), 4, 255, [255.0, 255.0, 255.0], None)
+                .collect::<Vec<[f64; 3]>>(),
+            vec![[255.0; 3], [0.0; 3], [204.0; 3]]
+        );
+    }
+
+    #[test]
+    fn rgb_decoder_transparent_key() {
+        let samples = vec![3, 1];
+        assert_eq!(
+            RgbDecoder::new(samples.into_iter(), 1, 3, [255.0, 0.0, 0.0], Some([3; 3]))
+                .collect::<Vec<[f64; 3]>>(),
+            vec![[255.0, 0.0, 0.0], [85.0; 3]]
+        );
     }
 }
@@ -0,0 +1,178 @@
+use clap::ValueEnum;
+
+const BAYER_BITS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Dither {
+    /// Floyd-Steinberg error diffusion
+    FloydSteinberg,
+    /// Atkinson error diffusion, passes on only 3/4 of the error for more contrast
+    Atkinson,
+    /// Jarvis-Judice-Ninke error diffusion over two rows
+    JarvisJudiceNinke,
+    /// Ordered dithering with an 8x8 Bayer matrix
+    Bayer,
+}
+
+impl Dither {
+    // the neighbors (dx, dy) that get a share of the error and the sum the shares get divided by
+    fn kernel(&self) -> (&'static [(isize, usize, f32)], f32) {
+        match self {
+            Self::FloydSteinberg => (&[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)], 16.0),
+            Self::Atkinson => (
+                &[
+                    (1, 0, 1.0),
+                    (2, 0, 1.0),
+                    (-1, 1, 1.0),
+                    (0, 1, 1.0),
+                    (1, 1, 1.0),
+                    (0, 2, 1.0),
+                ],
+                8.0,
+            ),
+            Self::JarvisJudiceNinke => (
+                &[
+                    (1, 0, 7.0),
+                    (2, 0, 5.0),
+                    (-2, 1, 3.0),
+                    (-1, 1, 5.0),
+                    (0, 1, 7.0),
+                    (1, 1, 5.0),
+                    (2, 1, 3.0),
+                    (-2, 2, 1.0),
+                    (-1, 2, 3.0),
+                    (0, 2, 5.0),
+                    (1, 2, 3.0),
+                    (2, 2, 1.0),
+                ],
+                48.0,
+            ),
+            Self::Bayer => (&[], 1.0),
+        }
+    }
+
+    // the black pixels of the values, values at the threshold get half of the pixels black
+    pub fn bits(
+        &self,
+        width: usize,
+        height: usize,
+        values: &[f32],
+        threshold: f64,
+        serpentine: bool,
+    ) -> Vec<bool> {
+        let threshold = (threshold as f32).clamp(f32::EPSILON, 1.0 - f32::EPSILON);
+        let mut values = values
+            .iter()
+            .map(|value| {
+                if *value < threshold {
+                    value / threshold / 2.0
+                } else {
+                    0.5 + (value - threshold) / (1.0 - threshold) / 2.0
+                }
+            })
+            .collect::<Vec<f32>>();
+
+        if *self == Self::Bayer {
+            let cells = (1 << (2 * BAYER_BITS)) as f32;
+            return values
+                .iter()
+                .enumerate()
+                .map(|(idx, value)| {
+                    let order = bayer(idx % width, idx / width) as f32;
+                    *value < (order + 0.5) / cells
+                })
+                .collect();
+        }
+
+        let (kernel, divisor) = self.kernel();
+        let mut bits = vec![false; values.len()];
+        for y in 0..height {
+            let backwards = serpentine && y % 2 == 1;
+            for step in 0..width {
+                let x = if backwards { width - 1 - step } else { step };
+                let value = values[y * width + x];
+                let black = value < 0.5;
+                bits[y * width + x] = black;
+                let error = value - if black { 0.0 } else { 1.0 };
+                for (dx, dy, weight) in kernel {
+                    let dx = if backwards { -dx } else { *dx };
+                    let (Some(x), y) = (x.checked_add_signed(dx), y + dy) else {
+                        continue;
+                    };
+                    if x < width && y < height {
+                        values[y * width + x] += error * weight / divisor;
+                    }
+                }
+            }
+        }
+        bits
+    }
+}
+
+// the position of a pixel in the order of the bayer matrix, the lowest bits decide the most
+fn bayer(x: usize, y: usize) -> usize {
+    (0..BAYER_BITS).fold(0, |order, bit| {
+        let (x, y) = ((x >> bit) & 1, (y >> bit) & 1);
+        order * 4 + 2 * (x ^ y) + y
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dithering_keeps_the_tone() {
+        let (width, height) = (64, 64);
+        let values = vec![0.25; width * height];
+        for dither in [
+            Dither::FloydSteinberg,
+            Dither::JarvisJudiceNinke,
+            Dither::Bayer,
+        ] {
+            for serpentine in [false, true] {
+                let bits = dither.bits(width, height, &values, 0.5, serpentine);
+                let black = bits.iter().filter(|bit| **bit).count() as f64 / bits.len() as f64;
+                assert!((black - 0.75).abs() < 0.02, "{dither:?} {black}");
+            }
+        }
+    }
+
+    #[test]
+    fn kernels_pass_on_the_error() {
+        for (dither, share) in [
+            (Dither::FloydSteinberg, 1.0),
+            (Dither::Atkinson, 0.75),
+            (Dither::JarvisJudiceNinke, 1.0),
+        ] {
+            let (kernel, divisor) = dither.kernel();
+            let weights = kernel.iter().map(|(_, _, weight)| weight).sum::<f32>();
+            assert_eq!(weights / divisor, share, "{dither:?}");
+            assert!(kernel.iter().all(|(dx, dy, _)| *dy > 0 || *dx > 0));
+        }
+
+        // the lost quarter of the error leaves mid gray alone but pushes darker grays to black
+        let (width, height) = (64, 64);
+        let black = |value: f32| {
+            let bits =
+                Dither::Atkinson.bits(width, height, &vec![value; width * height], 0.5, false);
+            bits.iter().filter(|bit| **bit).count() as f64 / bits.len() as f64
+        };
+        assert!((black(0.5) - 0.5).abs() < 0.02);
+        assert!(black(0.25) > 0.8);
+        assert!(black(0.75) < 0.2);
+    }
+
+    #[test]
+    fn bayer_matrix() {
+        assert_eq!(
+            (0..4).map(|x| bayer(x, 0)).collect::<Vec<usize>>(),
+            vec![0, 32, 8, 40]
+        );
+        let mut orders = (0..64)
+            .map(|idx| bayer(idx % 8, idx / 8))
+            .collect::<Vec<usize>>();
+        orders.sort();
+        assert_eq!(orders, (0..64).collect::<Vec<usize>>());
+    }
+}
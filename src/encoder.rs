@@ -36,3 +36,544 @@ impl<'a> Iterator for OneBitEncoder<'a> {
         Some(byte)
     }
 }
+
+const WHITE_TERMINATING: [(u16, u8); 64] = [
+    (0b00110101, 8),
+    (0b000111, 6),
+    (0b0111, 4),
+    (0b1000, 4),
+    (0b1011, 4),
+    (0b1100, 4),
+    (0b1110, 4),
+    (0b1111, 4),
+    (0b10011, 5),
+    (0b10100, 5),
+    (0b00111, 5),
+    (0b01000, 5),
+    (0b001000, 6),
+    (0b000011, 6),
+    (0b110100, 6),
+    (0b110101, 6),
+    (0b101010, 6),
+    (0b101011, 6),
+    (0b0100111, 7),
+    (0b0001100, 7),
+    (0b0001000, 7),
+    (0b0010111, 7),
+    (0b0000011, 7),
+    (0b0000100, 7),
+    (0b0101000, 7),
+    (0b0101011, 7),
+    (0b0010011, 7),
+    (0b0100100, 7),
+    (0b0011000, 7),
+    (0b00000010, 8),
+    (0b00000011, 8),
+    (0b00011010, 8),
+    (0b00011011, 8),
+    (0b00010010, 8),
+    (0b00010011, 8),
+    (0b00010100, 8),
+    (0b00010101, 8),
+    (0b00010110, 8),
+    (0b00010111, 8),
+    (0b00101000, 8),
+    (0b00101001, 8),
+    (0b00101010, 8),
+    (0b00101011, 8),
+    (0b00101100, 8),
+    (0b00101101, 8),
+    (0b00000100, 8),
+    (0b00000101, 8),
+    (0b00001010, 8),
+    (0b00001011, 8),
+    (0b01010010, 8),
+    (0b01010011, 8),
+    (0b01010100, 8),
+    (0b01010101, 8),
+    (0b00100100, 8),
+    (0b00100101, 8),
+    (0b01011000, 8),
+    (0b01011001, 8),
+    (0b01011010, 8),
+    (0b01011011, 8),
+    (0b01001010, 8),
+    (0b01001011, 8),
+    (0b00110010, 8),
+    (0b00110011, 8),
+    (0b00110100, 8),
+];
+
+const WHITE_MAKEUP: [(u16, u8); 27] = [
+    (0b11011, 5),
+    (0b10010, 5),
+    (0b010111, 6),
+    (0b0110111, 7),
+    (0b00110110, 8),
+    (0b00110111, 8),
+    (0b01100100, 8),
+    (0b01100101, 8),
+    (0b01101000, 8),
+    (0b01100111, 8),
+    (0b011001100, 9),
+    (0b011001101, 9),
+    (0b011010010, 9),
+    (0b011010011, 9),
+    (0b011010100, 9),
+    (0b011010101, 9),
+    (0b011010110, 9),
+    (0b011010111, 9),
+    (0b011011000, 9),
+    (0b011011001, 9),
+    (0b011011010, 9),
+    (0b011011011, 9),
+    (0b010011000, 9),
+    (0b010011001, 9),
+    (0b010011010, 9),
+    (0b011000, 6),
+    (0b010011011, 9),
+];
+
+const BLACK_TERMINATING: [(u16, u8); 64] = [
+    (0b0000110111, 10),
+    (0b010, 3),
+    (0b11, 2),
+    (0b10, 2),
+    (0b011, 3),
+    (0b0011, 4),
+    (0b0010, 4),
+    (0b00011, 5),
+    (0b000101, 6),
+    (0b000100, 6),
+    (0b0000100, 7),
+    (0b0000101, 7),
+    (0b0000111, 7),
+    (0b00000100, 8),
+    (0b00000111, 8),
+    (0b000011000, 9),
+    (0b0000010111, 10),
+    (0b0000011000, 10),
+    (0b0000001000, 10),
+    (0b00001100111, 11),
+    (0b00001101000, 11),
+    (0b00001101100, 11),
+    (0b00000110111, 11),
+    (0b00000101000, 11),
+    (0b00000010111, 11),
+    (0b00000011000, 11),
+    (0b000011001010, 12),
+    (0b000011001011, 12),
+    (0b000011001100, 12),
+    (0b000011001101, 12),
+    (0b000001101000, 12),
+    (0b000001101001, 12),
+    (0b000001101010, 12),
+    (0b000001101011, 12),
+    (0b000011010010, 12),
+    (0b000011010011, 12),
+    (0b000011010100, 12),
+    (0b000011010101, 12),
+    (0b000011010110, 12),
+    (0b000011010111, 12),
+    (0b000001101100, 12),
+    (0b000001101101, 12),
+    (0b000011011010, 12),
+    (0b000011011011, 12),
+    (0b000001010100, 12),
+    (0b000001010101, 12),
+    (0b000001010110, 12),
+    (0b000001010111, 12),
+    (0b000001100100, 12),
+    (0b000001100101, 12),
+    (0b000001010010, 12),
+    (0b000001010011, 12),
+    (0b000000100100, 12),
+    (0b000000110111, 12),
+    (0b000000111000, 12),
+    (0b000000100111, 12),
+    (0b000000101000, 12),
+    (0b000001011000, 12),
+    (0b000001011001, 12),
+    (0b000000101011, 12),
+    (0b000000101100, 12),
+    (0b000001011010, 12),
+    (0b000001100110, 12),
+    (0b000001100111, 12),
+];
+
+const BLACK_MAKEUP: [(u16, u8); 27] = [
+    (0b0000001111, 10),
+    (0b000011001000, 12),
+    (0b000011001001, 12),
+    (0b000001011011, 12),
+    (0b000000110011, 12),
+    (0b000000110100, 12),
+    (0b000000110101, 12),
+    (0b0000001101100, 13),
+    (0b0000001101101, 13),
+    (0b0000001001010, 13),
+    (0b0000001001011, 13),
+    (0b0000001001100, 13),
+    (0b0000001001101, 13),
+    (0b0000001110010, 13),
+    (0b0000001110011, 13),
+    (0b0000001110100, 13),
+    (0b0000001110101, 13),
+    (0b0000001110110, 13),
+    (0b0000001110111, 13),
+    (0b0000001010010, 13),
+    (0b0000001010011, 13),
+    (0b0000001010100, 13),
+    (0b0000001010101, 13),
+    (0b0000001011010, 13),
+    (0b0000001011011, 13),
+    (0b0000001100100, 13),
+    (0b0000001100101, 13),
+];
+
+const EXTENDED_MAKEUP: [(u16, u8); 13] = [
+    (0b00000001000, 11),
+    (0b00000001100, 11),
+    (0b00000001101, 11),
+    (0b000000010010, 12),
+    (0b000000010011, 12),
+    (0b000000010100, 12),
+    (0b000000010101, 12),
+    (0b000000010110, 12),
+    (0b000000010111, 12),
+    (0b000000011100, 12),
+    (0b000000011101, 12),
+    (0b000000011110, 12),
+    (0b000000011111, 12),
+];
+
+const EOL: (u16, u8) = (0b000000000001, 12);
+const PASS: (u16, u8) = (0b0001, 4);
+const HORIZONTAL: (u16, u8) = (0b001, 3);
+const VERTICAL: [(u16, u8); 7] = [
+    (0b0000010, 7),
+    (0b000010, 6),
+    (0b010, 3),
+    (0b1, 1),
+    (0b011, 3),
+    (0b000011, 6),
+    (0b0000011, 7),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct BitWriter {
+    data: Vec<u8>,
+    byte: u8,
+    bits: u8,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, code: u16, len: u8) {
+        for i in (0..len).rev() {
+            self.byte = (self.byte << 1) | ((code >> i) & 1) as u8;
+            self.bits += 1;
+            if self.bits == 8 {
+                self.data.push(self.byte);
+                self.byte = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    pub fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.align();
+        self.data
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CcittMode {
+    Group3,
+    Group4,
+}
+
+pub struct CcittEncoder {
+    writer: BitWriter,
+    width: usize,
+    mode: CcittMode,
+    reference: Vec<usize>,
+}
+
+impl CcittEncoder {
+    pub fn new(width: usize, mode: CcittMode) -> Self {
+        let writer = BitWriter::new();
+        let reference = Vec::new();
+        Self {
+            writer,
+            width,
+            mode,
+            reference,
+        }
+    }
+
+    pub fn encode_line<T>(&mut self, line: T)
+    where
+        T: IntoIterator<Item = bool>,
+    {
+        let changes = changing_elements(line);
+        match self.mode {
+            CcittMode::Group3 => {
+                self.writer.write(EOL.0, EOL.1);
+                let mut last = 0;
+                for (idx, change) in changes.iter().chain([self.width].iter()).enumerate() {
+                    self.write_run(change - last, idx % 2 == 1);
+                    last = *change;
+                }
+            }
+            CcittMode::Group4 => {
+                self.encode_two_dimensional(&changes);
+                self.reference = changes;
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        if self.mode == CcittMode::Group4 {
+            self.writer.write(EOL.0, EOL.1);
+            self.writer.write(EOL.0, EOL.1);
+        }
+        self.writer.finish()
+    }
+
+    fn encode_two_dimensional(&mut self, changes: &[usize]) {
+        let width = self.width;
+        let next = |changes: &[usize], a0: Option<usize>| -> usize {
+            let idx = match a0 {
+                Some(a0) => changes.partition_point(|change| *change <= a0),
+                None => 0,
+            };
+            changes.get(idx).copied().unwrap_or(width)
+        };
+
+        let mut a0: Option<usize> = None;
+        let mut black = false;
+        loop {
+            let a1 = next(changes, a0);
+            let a2 = next(changes, Some(a1));
+            let mut idx = match a0 {
+                Some(a0) => self.reference.partition_point(|change| *change <= a0),
+                None => 0,
+            };
+            // b1 has to change to the opposite color of a0, even indices change to black
+            if (idx % 2 == 1) != black {
+                idx += 1;
+            }
+            let b1 = self.reference.get(idx).copied().unwrap_or(self.width);
+            let b2 = self.reference.get(idx + 1).copied().unwrap_or(self.width);
+
+            if b2 < a1 {
+                self.writer.write(PASS.0, PASS.1);
+                a0 = Some(b2);
+            } else if a1.abs_diff(b1) <= 3 {
+                let (code, len) = VERTICAL[(a1 as isize - b1 as isize + 3) as usize];
+                self.writer.write(code, len);
+                a0 = Some(a1);
+                black = !black;
+            } else {
+                self.writer.write(HORIZONTAL.0, HORIZONTAL.1);
+                self.write_run(a1 - a0.unwrap_or(0), black);
+                self.write_run(a2 - a1, !black);
+                a0 = Some(a2);
+            }
+
+            if a0.is_some_and(|a0| a0 >= self.width) {
+                break;
+            }
+        }
+    }
+
+    fn write_run(&mut self, mut run: usize, black: bool) {
+        let (terminating, makeup) = if black {
+            (&BLACK_TERMINATING, &BLACK_MAKEUP)
+        } else {
+            (&WHITE_TERMINATING, &WHITE_MAKEUP)
+        };
+        while run >= 2560 {
+            let (code, len) = EXTENDED_MAKEUP[12];
+            self.writer.write(code, len);
+            run -= 2560;
+        }
+        if run >= 1792 {
+            let (code, len) = EXTENDED_MAKEUP[(run - 1792) / 64];
+            self.writer.write(code, len);
+            run %= 64;
+        } else if run >= 64 {
+            let (code, len) = makeup[run / 64 - 1];
+            self.writer.write(code, len);
+            run %= 64;
+        }
+        let (code, len) = terminating[run];
+        self.writer.write(code, len);
+    }
+}
+
+fn changing_elements<T>(line: T) -> Vec<usize>
+where
+    T: IntoIterator<Item = bool>,
+{
+    let mut last = false;
+    let mut changes = Vec::new();
+    for (x, pixel) in line.into_iter().enumerate() {
+        if pixel != last {
+            changes.push(x);
+            last = pixel;
+        }
+    }
+    changes
+}
+
+pub struct PackBitsEncoder<'a> {
+    data: &'a [u8],
+    idx: usize,
+}
+
+impl<'a> PackBitsEncoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        let idx = 0;
+        Self { data, idx }
+    }
+}
+
+impl<'a> Iterator for PackBitsEncoder<'a> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx >= self.data.len() {
+            return None;
+        }
+
+        let rest = &self.data[self.idx..];
+        let repeated = rest
+            .iter()
+            .take(128)
+            .take_while(|byte| **byte == rest[0])
+            .count();
+        if repeated > 1 {
+            self.idx += repeated;
+            return Some(vec![(1 - repeated as i16) as u8, rest[0]]);
+        }
+
+        let mut literal = 1;
+        while literal < rest.len().min(128)
+            && (literal + 1 >= rest.len() || rest[literal] != rest[literal + 1])
+        {
+            literal += 1;
+        }
+        self.idx += literal;
+        let mut packet = vec![(literal - 1) as u8];
+        packet.extend(&rest[..literal]);
+        Some(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+
+    #[test]
+    fn pack_bits_encoder() {
+        let data = [0xaa, 0xaa, 0xaa, 0x80, 0x00, 0x2a, 0xaa, 0xaa];
+        assert_eq!(
+            PackBitsEncoder::new(&data).flatten().collect::<Vec<u8>>(),
+            vec![0xfe, 0xaa, 0x02, 0x80, 0x00, 0x2a, 0xff, 0xaa]
+        );
+    }
+
+    #[test]
+    fn ccitt_group4_blank_line() {
+        let mut encoder = CcittEncoder::new(8, CcittMode::Group4);
+        encoder.encode_line([false; 8]);
+        // V0 for the blank line followed by EOFB
+        assert_eq!(encoder.finish(), vec![0x80, 0x08, 0x00, 0x80]);
+    }
+
+    fn bits(data: &[u8]) -> String {
+        data.iter().map(|byte| format!("{byte:08b}")).collect()
+    }
+
+    // the codes of the T.4 and T.6 tables, padded with zeros to whole bytes
+    fn codes(codes: &[&str]) -> String {
+        let mut bits = codes.concat().replace(' ', "");
+        while bits.len() % 8 != 0 {
+            bits.push('0');
+        }
+        bits
+    }
+
+    #[test]
+    fn ccitt_group4_modes() {
+        let lines = [
+            // horizontal with white 2 and black 2 on the blank reference, then V0
+            ("..##....", "001 0111 11 1"),
+            ("..##....", "1 1 1"),
+            // VR1 twice
+            ("...##...", "011 011 1"),
+            // pass over the black run of the reference
+            ("........", "0001 1"),
+            // VL3 and VL1 from the end of the blank reference
+            (".....##.", "0000010 010 1"),
+            ("..##....", "0000010 0000010 0001 1"),
+            // VR2 then VL3
+            ("....#...", "000011 0000010 1"),
+            ("...#....", "010 010 1"),
+            // pass, VL3 and VL2
+            (".....#..", "0001 0000010 000010 1"),
+            (".####...", "001 000111 011 1"),
+            ("....#...", "0000011 1 1"),
+        ];
+        let mut encoder = CcittEncoder::new(8, CcittMode::Group4);
+        let mut expected = Vec::new();
+        for (pattern, code) in lines {
+            encoder.encode_line(Image::from_rows(&[pattern]).line(0));
+            expected.push(code);
+        }
+        // EOFB
+        expected.push("000000000001 000000000001");
+        assert_eq!(bits(&encoder.finish()), codes(&expected));
+    }
+
+    #[test]
+    fn ccitt_group3_runs() {
+        let eol = "000000000001";
+        let runs = |width: usize, black_runs: &[(usize, usize)], expected: &[&str]| {
+            let mut pixels = vec![false; width];
+            for (start, len) in black_runs {
+                pixels[*start..start + len].fill(true);
+            }
+            let mut encoder = CcittEncoder::new(width, CcittMode::Group3);
+            encoder.encode_line(pixels);
+            assert_eq!(bits(&encoder.finish()), codes(expected));
+        };
+        // white 3, black 2, white 15
+        runs(20, &[(3, 2)], &[eol, "1000 11 110101"]);
+        // a line starting black starts with white 0
+        runs(4, &[(0, 4)], &[eol, "00110101 011"]);
+        // white 64 + 6, black 128 + 2
+        runs(200, &[(70, 130)], &[eol, "11011 1110", "000011001000 11"]);
+        // the extended makeup codes of 1984 and 1792 are shared by both colors
+        runs(2000, &[], &[eol, "000000010010 101010"]);
+        runs(
+            2563 + 1800,
+            &[(2563, 1800)],
+            &[eol, "000000011111 1000", "00000001000 000101"],
+        );
+        // runs over 2560 repeat its makeup code
+        runs(5125, &[], &[eol, "000000011111 000000011111 1100"]);
+    }
+}
@@ -0,0 +1,477 @@
+use crate::background::flatten;
+use crate::convert::Quantizer;
+use crate::decoder::{composite_palette, DecodeOptions, RgbDecoder, SampleDecoder};
+use crate::dither::Dither;
+use crate::image::Image;
+use crate::label::Connectivity;
+use crate::metadata::Metadata;
+use crate::netpbm::Netpbm;
+use crate::threshold::{Local, Method};
+use std::error::Error;
+use std::io::Read;
+
+pub const HISTOGRAM_BINS: usize = 256;
+
+// the distance of every pixel to the color that is read in, 0 is the color itself and 1 the farthest
+#[derive(Debug, Clone)]
+pub struct GrayImage {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+    metadata: Metadata,
+}
+
+impl GrayImage {
+    pub fn new(width: usize, height: usize, data: Vec<f32>) -> Self {
+        let metadata = Metadata::default();
+        Self {
+            width,
+            height,
+            data,
+            metadata,
+        }
+    }
+
+    pub fn from_png_reader(
+        reader: impl Read,
+        options: &DecodeOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (image, metadata) = decode_png(reader, options, |width, height, distances| {
+            Self::new(width, height, distances.map(|d| d as f32).collect())
+        })?;
+        Ok(image.with_metadata(metadata))
+    }
+
+    pub fn from_pnm_reader(
+        reader: impl Read,
+        options: &DecodeOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        decode_pnm(reader, options, |width, height, distances| {
+            Self::new(width, height, distances.map(|d| d as f32).collect())
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[y * self.width + x]
+    }
+
+    pub fn histogram(&self) -> [usize; HISTOGRAM_BINS] {
+        let mut histogram = [0; HISTOGRAM_BINS];
+        for value in &self.data {
+            histogram[bin(*value)] += 1;
+        }
+        histogram
+    }
+
+    // a threshold on the histogram bins, so that exactly the pixels of the lower bins get black
+    pub fn auto_threshold(&self, method: Method) -> f64 {
+        let bin = method.level(&self.histogram());
+        (bin + 1) as f64 / HISTOGRAM_BINS as f64
+    }
+
+    pub fn local_threshold(&self, local: Local, window: usize, k: f64) -> Image {
+        let integral = IntegralImage::new(self);
+        let radius = window / 2;
+        let bits = (0..self.height).flat_map(|y| {
+            let integral = &integral;
+            (0..self.width).map(move |x| {
+                let (count, sum, squares) = integral.window(
+                    x.saturating_sub(radius),
+                    y.saturating_sub(radius),
+                    (x + radius + 1).min(self.width),
+                    (y + radius + 1).min(self.height),
+                );
+                let mean = sum / count;
+                let deviation = (squares / count - mean * mean).max(0.0).sqrt();
+                (self.get(x, y) as f64) < local.threshold(mean, deviation, k)
+            })
+        });
+        Image::from_bits(self.width, self.height, bits).with_metadata(self.metadata.clone())
+    }
+
+    pub fn dither(&self, dither: Dither, threshold: f64, serpentine: bool) -> Image {
+        let bits = dither.bits(self.width, self.height, &self.data, threshold, serpentine);
+        Image::from_bits(self.width, self.height, bits).with_metadata(self.metadata.clone())
+    }
+
+    // the pixels below the weak threshold that connect to a pixel below the strong one
+    pub fn hysteresis(&self, strong: f64, weak: f64, connectivity: Connectivity) -> Image {
+        self.threshold(weak)
+            .connected_to(&self.threshold(strong), connectivity)
+    }
+
+    pub fn threshold(&self, threshold: f64) -> Image {
+        Image::from_bits(
+            self.width,
+            self.height,
+            self.data.iter().map(|value| (*value as f64) < threshold),
+        )
+        .with_metadata(self.metadata.clone())
+    }
+}
+
+// the sums and squared sums of all values above and left of every pixel
+struct IntegralImage {
+    width: usize,
+    sums: Vec<f64>,
+    squares: Vec<f64>,
+}
+
+impl IntegralImage {
+    fn new(image: &GrayImage) -> Self {
+        let width = image.width + 1;
+        let mut sums = vec![0.0; width * (image.height + 1)];
+        let mut squares = vec![0.0; width * (image.height + 1)];
+        for y in 0..image.height {
+            let mut sum = 0.0;
+            let mut square = 0.0;
+            for x in 0..image.width {
+                let value = image.get(x, y) as f64;
+                sum += value;
+                square += value * value;
+                sums[(y + 1) * width + x + 1] = sums[y * width + x + 1] + sum;
+                squares[(y + 1) * width + x + 1] = squares[y * width + x + 1] + square;
+            }
+        }
+        Self {
+            width,
+            sums,
+            squares,
+        }
+    }
+
+    // the pixel count, sum and squared sum of the values in x0..x1 and y0..y1
+    fn window(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> (f64, f64, f64) {
+        let area = |values: &[f64]| {
+            values[y1 * self.width + x1]
+                - values[y0 * self.width + x1]
+                - values[y1 * self.width + x0]
+                + values[y0 * self.width + x0]
+        };
+        (
+            ((x1 - x0) * (y1 - y0)) as f64,
+            area(&self.sums),
+            area(&self.squares),
+        )
+    }
+}
+
+fn bin(value: f32) -> usize {
+    ((value * HISTOGRAM_BINS as f32) as usize).min(HISTOGRAM_BINS - 1)
+}
+
+// the composited pixels of an image, palette images keep their indices
+pub enum Pixels<'a> {
+    Rgb(&'a mut dyn Iterator<Item = [f64; 3]>),
+    Indexed(&'a [u8], &'a mut dyn Iterator<Item = u16>),
+}
+
+pub fn read_png<T, F>(
+    reader: impl Read,
+    background: [f64; 3],
+    collect: F,
+) -> Result<(T, Metadata), Box<dyn Error>>
+where
+    F: FnOnce(usize, usize, Pixels) -> T,
+{
+    let decoder = png::Decoder::new(reader);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf)?;
+    let info = reader.info();
+    let metadata = Metadata::from_png_info(info);
+
+    let width = info.width as usize;
+    let height = info.height as usize;
+    let bit_depth = frame.bit_depth as usize;
+    let channels = frame.color_type.samples();
+    let rows = buf[..frame.line_size * height].chunks(frame.line_size);
+
+    if frame.color_type != png::ColorType::Indexed {
+        let transparent = match info.trns.as_deref() {
+            Some(&[g]) => Some([g as u16; 3]),
+            Some(&[r, g, b]) => Some([r as u16, g as u16, b as u16]),
+            Some(&[g0, g1]) => Some([u16::from_be_bytes([g0, g1]); 3]),
+            Some(&[r0, r1, g0, g1, b0, b1]) => Some([
+                u16::from_be_bytes([r0, r1]),
+                u16::from_be_bytes([g0, g1]),
+                u16::from_be_bytes([b0, b1]),
+            ]),
+            _ => None,
+        };
+        let mut pixels = rows.flat_map(|row| {
+            RgbDecoder::new(
+                SampleDecoder::new(row, bit_depth).take(width * channels),
+                channels,
+                ((1u32 << bit_depth) - 1) as u16,
+                background,
+                transparent,
+            )
+        });
+        let result = collect(width, height, Pixels::Rgb(&mut pixels));
+        return Ok((result, metadata));
+    }
+
+    let palette = composite_palette(
+        info.palette
+            .as_ref()
+            .ok_or("try to access pallette, but it's not there".to_string())?,
+        info.trns.as_deref(),
+        background,
+    );
+    // a corrupt image can point past its palette, which the png crate doesn't check
+    let indices = || {
+        rows.clone()
+            .flat_map(|row| SampleDecoder::new(row, bit_depth).take(width))
+    };
+    let colors = palette.len() / 3;
+    if let Some(index) = indices().find(|index| *index as usize >= colors) {
+        return Err(
+            format!("the palette index {index} is out of the {colors} palette colors").into(),
+        );
+    }
+    let result = collect(width, height, Pixels::Indexed(&palette, &mut indices()));
+    Ok((result, metadata))
+}
+
+pub fn read_pnm<T, F>(
+    mut reader: impl Read,
+    background: [f64; 3],
+    collect: F,
+) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce(usize, usize, Pixels) -> T,
+{
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let pnm = Netpbm::decode(&data)?;
+    let mut pixels = RgbDecoder::new(
+        pnm.samples().iter().copied(),
+        pnm.channels(),
+        pnm.max_value(),
+        background,
+        None,
+    );
+    Ok(collect(pnm.width(), pnm.height(), Pixels::Rgb(&mut pixels)))
+}
+
+pub fn decode_png<T, F>(
+    reader: impl Read,
+    options: &DecodeOptions,
+    collect: F,
+) -> Result<(T, Metadata), Box<dyn Error>>
+where
+    F: FnOnce(usize, usize, &mut dyn Iterator<Item = f64>) -> T,
+{
+    read_png(reader, options.background, |width, height, pixels| {
+        distances(width, height, pixels, options, collect)
+    })
+}
+
+pub fn decode_pnm<T, F>(
+    reader: impl Read,
+    options: &DecodeOptions,
+    collect: F,
+) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce(usize, usize, &mut dyn Iterator<Item = f64>) -> T,
+{
+    read_pnm(reader, options.background, |width, height, pixels| {
+        distances(width, height, pixels, options, collect)
+    })
+}
+
+fn distances<T, F>(
+    width: usize,
+    height: usize,
+    pixels: Pixels,
+    options: &DecodeOptions,
+    collect: F,
+) -> T
+where
+    F: FnOnce(usize, usize, &mut dyn Iterator<Item = f64>) -> T,
+{
+    if let Some(method) = options.flatten {
+        // the background differs from pixel to pixel, so palette images get expanded
+        let mut rgb = match pixels {
+            Pixels::Rgb(pixels) => pixels
+                .map(|pixel| pixel.map(|channel| channel.round() as u8))
+                .collect::<Vec<[u8; 3]>>(),
+            Pixels::Indexed(palette, indices) => indices
+                .map(|index| {
+                    let idx = index as usize * 3;
+                    [palette[idx], palette[idx + 1], palette[idx + 2]]
+                })
+                .collect(),
+        };
+        flatten(width, height, &mut rgb, method);
+        let pixels = rgb
+            .into_iter()
+            .map(|pixel| pixel.map(|channel| channel as f64));
+        return pixel_distances(width, height, pixels, options, collect);
+    }
+
+    let (palette, indices) = match pixels {
+        Pixels::Rgb(pixels) => return pixel_distances(width, height, pixels, options, collect),
+        Pixels::Indexed(palette, indices) => (palette, indices),
+    };
+
+    if let Some(luminance) = options.luminance {
+        let palette = palette
+            .chunks(3)
+            .map(|rgb| luminance.level(rgb[0] as f64, rgb[1] as f64, rgb[2] as f64))
+            .collect::<Vec<u8>>();
+        let levels = indices.map(|index| palette[index as usize]).collect();
+        return level_distances(width, height, levels, options, collect);
+    }
+
+    let matcher = options.matcher();
+    let palette = palette
+        .chunks(3)
+        .map(|rgb| matcher.distance([rgb[0] as f64, rgb[1] as f64, rgb[2] as f64]))
+        .collect::<Vec<f64>>();
+    collect(
+        width,
+        height,
+        &mut indices.map(|index| palette[index as usize]),
+    )
+}
+
+fn pixel_distances<I, T, F>(
+    width: usize,
+    height: usize,
+    pixels: I,
+    options: &DecodeOptions,
+    collect: F,
+) -> T
+where
+    I: Iterator<Item = [f64; 3]>,
+    F: FnOnce(usize, usize, &mut dyn Iterator<Item = f64>) -> T,
+{
+    if let Some(luminance) = options.luminance {
+        let levels = pixels.map(|[r, g, b]| luminance.level(r, g, b)).collect();
+        return level_distances(width, height, levels, options, collect);
+    }
+    let matcher = options.matcher();
+    collect(
+        width,
+        height,
+        &mut pixels.map(|pixel| matcher.distance(pixel)),
+    )
+}
+
+fn level_distances<T, F>(
+    width: usize,
+    height: usize,
+    levels: Vec<u8>,
+    options: &DecodeOptions,
+    collect: F,
+) -> T
+where
+    F: FnOnce(usize, usize, &mut dyn Iterator<Item = f64>) -> T,
+{
+    let quantizer = Quantizer::from_levels(levels.iter().copied(), options.colors.unwrap_or(256));
+    let matcher = options.matcher();
+    let palette = quantizer
+        .palette()
+        .iter()
+        .map(|level| matcher.distance([*level as f64; 3]))
+        .collect::<Vec<f64>>();
+    collect(
+        width,
+        height,
+        &mut levels
+            .into_iter()
+            .map(|level| palette[quantizer.index(level) as usize]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::background::Flatten;
+    use crate::cluster::ColorHistogram;
+
+    #[test]
+    fn hysteresis_keeps_connected_faint_pixels() {
+        // a stroke that fades out and a faint speck
+        let mut data = vec![1.0; 10 * 3];
+        for (x, value) in [0.1, 0.2, 0.6, 0.7, 0.7].into_iter().enumerate() {
+            data[10 + x] = value;
+        }
+        data[10 + 8] = 0.6;
+        let image = GrayImage::new(10, 3, data).hysteresis(0.5, 0.8, Connectivity::Four);
+        assert!((0..5).all(|x| image.get(x, 1)));
+        assert!(!image.get(8, 1));
+    }
+
+    #[test]
+    fn sauvola_follows_gradient() {
+        // a dark stroke on a background that gets darker than the stroke of the other side
+        let (width, height) = (60, 9);
+        let data = (0..height)
+            .flat_map(|y| {
+                (0..width).map(move |x| {
+                    let background = 0.9 - 0.6 * x as f32 / width as f32;
+                    if y == 4 {
+                        background - 0.25
+                    } else {
+                        background
+                    }
+                })
+            })
+            .collect();
+        let gray = GrayImage::new(width, height, data);
+        assert!(gray.threshold(0.5).get(5, 0) == gray.threshold(0.5).get(5, 4));
+
+        let image = gray.local_threshold(Local::Sauvola, 9, 0.2);
+        for x in 0..width {
+            assert!(image.get(x, 4), "{x}");
+            assert!(!image.get(x, 0), "{x}");
+        }
+    }
+
+    #[test]
+    fn palette_index_out_of_the_palette() {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 4, 1);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(vec![0, 0, 0, 255, 255, 255]);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0, 1, 5, 1]).unwrap();
+        writer.finish().unwrap();
+
+        let mut options = DecodeOptions::new(0.0, 0.0, 0.0, 0.5);
+        let error = Image::from_png_reader(png.as_slice(), &options).unwrap_err();
+        assert!(error.to_string().contains("palette index 5"));
+        assert!(GrayImage::from_png_reader(png.as_slice(), &options).is_err());
+        assert!(read_png(png.as_slice(), [255.0; 3], |_, _, pixels| {
+            ColorHistogram::new(pixels)
+        })
+        .is_err());
+        options.flatten = Some(Flatten::Closing(15));
+        assert!(Image::from_png_reader(png.as_slice(), &options).is_err());
+    }
+}
@@ -1,11 +1,19 @@
+use crate::contour::{trace, Contour};
 use crate::cutout::Cutout;
-use crate::decoder::{IndexedDecoder, ThreeByteDecoder};
-use crate::encoder::OneBitEncoder;
-use crate::object::{Object, Pixel};
+use crate::decoder::DecodeOptions;
+use crate::gray::{decode_png, decode_pnm};
+use crate::keep::Keep;
+use crate::label::{Connectivity, Labels, ObjectFilter};
+use crate::metadata::Metadata;
+use crate::netpbm::encode_pbm;
+use crate::object::Pixel;
+use crate::pdf::{encode_pdf, Paper};
+use crate::svg::encode_svg;
+use crate::tiff::{encode_tiff, Compression};
 use derivative::Derivative;
 use std::error::Error;
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufWriter, Read, Write};
 use std::path::Path;
 
 #[derive(Derivative)]
@@ -13,26 +21,93 @@ use std::path::Path;
 pub struct Image {
     width: usize,
     height: usize,
+    stride: usize,
     #[derivative(Debug = "ignore")]
-    data: Vec<bool>,
+    data: Vec<u64>,
+    metadata: Metadata,
+}
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+// the first len pixels of a word, pixels are stored from the most significant bit on
+fn mask(len: usize) -> u64 {
+    if len >= WORD_BITS {
+        u64::MAX
+    } else {
+        !(u64::MAX >> len)
+    }
 }
 
 impl Image {
     pub fn new_empty(width: usize, height: usize) -> Self {
-        let data = vec![false; width * height];
+        let stride = width.div_ceil(WORD_BITS);
+        let data = vec![0; stride * height];
+        let metadata = Metadata::default();
         Self {
             width,
             height,
+            stride,
             data,
+            metadata,
         }
     }
 
     pub fn new(width: usize, height: usize, data: Vec<bool>) -> Self {
-        Self {
+        Self::from_bits(width, height, data)
+    }
+
+    // an image drawn as rows of # for black and . for white
+    #[cfg(test)]
+    pub fn from_rows(rows: &[&str]) -> Self {
+        let width = rows.first().map_or(0, |row| row.len());
+        Self::from_bits(
+            width,
+            rows.len(),
+            rows.iter().flat_map(|row| row.chars().map(|c| c == '#')),
+        )
+    }
+
+    // rows of 1 bit per pixel, set bits are black when black_is_one
+    #[cfg(test)]
+    pub fn from_packed(width: usize, height: usize, data: &[u8], black_is_one: bool) -> Self {
+        let stride = width.div_ceil(8);
+        Self::from_bits(
             width,
             height,
-            data,
+            (0..height).flat_map(|y| {
+                (0..width).map(move |x| {
+                    (data[y * stride + x / 8] >> (7 - x % 8) & 1 == 1) == black_is_one
+                })
+            }),
+        )
+    }
+
+    // both diagonals, a page for the encoder tests whose width needs no whole bytes
+    #[cfg(test)]
+    pub fn crossed(width: usize, height: usize) -> Self {
+        let mut image = Self::new_empty(width, height);
+        for idx in 0..width.min(height) {
+            image.set(idx, idx, true);
+            image.set(width - 1 - idx, idx, true);
         }
+        image
+    }
+
+    pub fn from_bits<T>(width: usize, height: usize, bits: T) -> Self
+    where
+        T: IntoIterator<Item = bool>,
+    {
+        let mut image = Self::new_empty(width, height);
+        let mut bits = bits.into_iter();
+        for line in image.data.chunks_mut(image.stride.max(1)) {
+            for (idx, word) in line.iter_mut().enumerate() {
+                for bit in bits.by_ref().take(WORD_BITS.min(width - idx * WORD_BITS)) {
+                    *word = *word << 1 | bit as u64;
+                }
+                *word <<= WORD_BITS - (width - idx * WORD_BITS).min(WORD_BITS);
+            }
+        }
+        image
     }
 
     pub fn width(&self) -> usize {
@@ -43,6 +118,23 @@ impl Image {
         self.height
     }
 
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn dpi(&self) -> (f64, f64) {
+        self.metadata.dpi()
+    }
+
     pub fn has_pixel(&self, pixel: &Pixel) -> bool {
         self.get(pixel.x(), pixel.y())
     }
@@ -57,47 +149,57 @@ impl Image {
         clean_border: bool,
     ) -> Result<Self, Box<dyn Error>> {
         let image = Self::from_png(path, red, green, blue, tcolor)?;
-        let image = if tobj > 0 {
-            let mut imager = Self::new_empty(image.width, image.height);
-            for object in image
-                .full_cutout()
-                .objects(false)
-                .into_iter()
-                .filter(|object| {
-                    object.size() >= tobj
-                        && (!object.touches_border(image.width(), image.height(), 0, 0)
-                            || !clean_border)
-                })
-            {
-                for object in image
-                    .full_cutout()
-                    .cutout(
-                        object.width(),
-                        object.height(),
-                        object.xmin(),
-                        object.ymin(),
-                    )
-                    .objects(true)
-                    .into_iter()
-                    .filter(|obj| {
-                        obj.size() < tobj
-                            && !obj.touches_border(
-                                object.width(),
-                                object.height(),
-                                object.xmin(),
-                                object.ymin(),
-                            )
-                    })
-                {
-                    imager.set_pixels(object)
-                }
-                imager.set_pixels(object)
+        if tobj == 0 {
+            return Ok(image);
+        }
+        Ok(
+            ObjectFilter::new(&image, tobj, clean_border, Connectivity::Four, None)
+                .fill_holes(tobj - 1, f64::INFINITY)
+                .to_image()
+                .with_metadata(image.metadata),
+        )
+    }
+
+    // removes the objects smaller than tobj or not matching keep
+    pub fn filter_objects(
+        self,
+        tobj: usize,
+        clean_border: bool,
+        connectivity: Connectivity,
+        keep: Option<&Keep>,
+    ) -> Self {
+        if tobj == 0 && keep.is_none() {
+            return self;
+        }
+        ObjectFilter::new(&self, tobj, clean_border, connectivity, keep)
+            .to_image()
+            .with_metadata(self.metadata)
+    }
+
+    // fills the holes of up to max_size pixels and max_ratio times the size of the object around them
+    pub fn fill_holes(self, max_size: usize, max_ratio: f64, connectivity: Connectivity) -> Self {
+        ObjectFilter::new(&self, 0, false, connectivity, None)
+            .fill_holes(max_size, max_ratio)
+            .to_image()
+            .with_metadata(self.metadata)
+    }
+
+    // the objects that contain at least one black pixel of the seeds
+    pub fn connected_to(&self, seeds: &Image, connectivity: Connectivity) -> Self {
+        let labels = Labels::new(self, true, connectivity);
+        let mut seeded = vec![false; labels.components().len()];
+        for pixel in seeds.full_cutout().pixels(false, true) {
+            if let Some(label) = labels.label(pixel.x(), pixel.y()) {
+                seeded[label] = true;
             }
-            imager
-        } else {
-            image
-        };
-        Ok(image)
+        }
+        let bits = (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)));
+        Self::from_bits(
+            self.width,
+            self.height,
+            bits.map(|(x, y)| labels.label(x, y).is_some_and(|label| seeded[label])),
+        )
+        .with_metadata(self.metadata.clone())
     }
 
     pub fn from_png(
@@ -107,83 +209,272 @@ impl Image {
         blue: f64,
         threshold: f64,
     ) -> Result<Self, Box<dyn Error>> {
-        let file = File::open(path)?;
-        let decoder = png::Decoder::new(file);
-        let mut reader = decoder.read_info()?;
-        let info = reader.info();
-
-        let width = info.width as usize;
-        let height = info.height as usize;
-        let mut data: Vec<bool> = Vec::with_capacity(width * height);
-
-        if info.color_type == png::ColorType::Indexed && info.bit_depth == png::BitDepth::Eight {
-            let palette = ThreeByteDecoder::new(
-                &*info
-                    .palette
-                    .clone()
-                    .ok_or("try to access pallette, but it's not there".to_string())?,
-                red,
-                green,
-                blue,
-                threshold,
-            )
-            .collect::<Vec<bool>>();
-
-            while let Some(row) = reader.next_row()? {
-                data.extend(IndexedDecoder::new(&palette, row.data()))
-            }
-        } else {
-            return Err(format!(
-                "unsupported png variant (color_type={:?}, bit_depth={:?})",
-                info.color_type, info.bit_depth,
-            )
-            .into());
-        }
+        Self::from_png_with_options(path, &DecodeOptions::new(red, green, blue, threshold))
+    }
 
-        Ok(Self {
-            width,
-            height,
-            data,
+    pub fn from_png_with_options(
+        path: impl AsRef<Path>,
+        options: &DecodeOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::from_png_reader(File::open(path)?, options)
+    }
+
+    pub fn from_png_reader(
+        reader: impl Read,
+        options: &DecodeOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (image, metadata) = decode_png(reader, options, |width, height, distances| {
+            Self::from_bits(width, height, distances.map(|d| d < options.threshold))
+        })?;
+        Ok(image.with_metadata(metadata))
+    }
+
+    pub fn from_pnm(
+        path: impl AsRef<Path>,
+        red: f64,
+        green: f64,
+        blue: f64,
+        threshold: f64,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::from_pnm_with_options(path, &DecodeOptions::new(red, green, blue, threshold))
+    }
+
+    pub fn from_pnm_with_options(
+        path: impl AsRef<Path>,
+        options: &DecodeOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::from_pnm_reader(File::open(path)?, options)
+    }
+
+    pub fn from_pnm_reader(
+        reader: impl Read,
+        options: &DecodeOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        decode_pnm(reader, options, |width, height, distances| {
+            Self::from_bits(width, height, distances.map(|d| d < options.threshold))
         })
     }
 
     pub fn to_png(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
-        let file = File::create(path).unwrap();
-        let ref mut w = BufWriter::new(file);
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
 
-        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+    pub fn write_png(&self, writer: impl Write) -> Result<(), Box<dyn Error>> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
         encoder.set_color(png::ColorType::Grayscale);
         encoder.set_depth(png::BitDepth::One);
+        self.metadata.set_png_info(&mut encoder)?;
 
+        let padding = mask(8 - (8 - self.width % 8) % 8) >> (WORD_BITS - 8);
         let data = (0..self.height)
-            .map(|y| &self.data[y * self.width..(y + 1) * self.width])
-            .map(|line| OneBitEncoder::new(line))
-            .flatten()
+            .flat_map(|y| {
+                let mut bytes = self.packed_line(y);
+                for byte in bytes.iter_mut() {
+                    *byte = !*byte;
+                }
+                if let Some(last) = bytes.last_mut() {
+                    *last &= padding as u8;
+                }
+                bytes
+            })
             .collect::<Vec<u8>>();
 
         let mut writer = encoder.write_header()?;
+        self.metadata.write_png_chunks(&mut writer)?;
         writer.write_image_data(&data)?;
 
         Ok(())
     }
 
+    pub fn to_pbm(&self, path: impl AsRef<Path>, plain: bool) -> Result<(), Box<dyn Error>> {
+        self.write_pbm(File::create(path)?, plain)
+    }
+
+    pub fn write_pbm(&self, mut writer: impl Write, plain: bool) -> Result<(), Box<dyn Error>> {
+        let rows = (0..self.height)
+            .map(|y| self.line(y).collect())
+            .collect::<Vec<Vec<bool>>>();
+        writer.write_all(&encode_pbm(
+            self.width,
+            self.height,
+            rows.iter().map(Vec::as_slice),
+            plain,
+        ))?;
+        Ok(())
+    }
+
+    pub fn to_tiff(
+        &self,
+        path: impl AsRef<Path>,
+        compression: Compression,
+    ) -> Result<(), Box<dyn Error>> {
+        self.write_tiff(File::create(path)?, compression)
+    }
+
+    pub fn write_tiff(
+        &self,
+        mut writer: impl Write,
+        compression: Compression,
+    ) -> Result<(), Box<dyn Error>> {
+        writer.write_all(&encode_tiff(&[self], compression))?;
+        Ok(())
+    }
+
+    pub fn to_pdf(
+        &self,
+        path: impl AsRef<Path>,
+        compression: Compression,
+        paper: Option<Paper>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.write_pdf(File::create(path)?, compression, paper)
+    }
+
+    pub fn write_pdf(
+        &self,
+        mut writer: impl Write,
+        compression: Compression,
+        paper: Option<Paper>,
+    ) -> Result<(), Box<dyn Error>> {
+        writer.write_all(&encode_pdf(&[self], compression, paper))?;
+        Ok(())
+    }
+
+    pub fn to_svg(
+        &self,
+        path: impl AsRef<Path>,
+        epsilon: f64,
+        smoothing: f64,
+        connectivity: Connectivity,
+    ) -> Result<(), Box<dyn Error>> {
+        self.write_svg(File::create(path)?, epsilon, smoothing, connectivity)
+    }
+
+    // one path per object, with the outline and the outlines of its holes
+    pub fn write_svg(
+        &self,
+        mut writer: impl Write,
+        epsilon: f64,
+        smoothing: f64,
+        connectivity: Connectivity,
+    ) -> Result<(), Box<dyn Error>> {
+        let labels = Labels::new(self, true, connectivity);
+        let objects = (0..labels.components().len())
+            .map(|idx| {
+                trace(labels.object(idx), connectivity)
+                    .into_iter()
+                    .map(|contour| contour.simplify(epsilon))
+                    .collect()
+            })
+            .collect::<Vec<Vec<Contour>>>();
+        writer.write_all(encode_svg(self.width, self.height, &objects, smoothing).as_bytes())?;
+        Ok(())
+    }
+
     pub fn get(&self, x: usize, y: usize) -> bool {
-        let idx = y * self.width + x;
-        if idx >= self.data.len() {
+        if x >= self.width || y >= self.height {
             return false;
         }
-        self.data[idx]
+        self.data[y * self.stride + x / WORD_BITS] >> (WORD_BITS - 1 - x % WORD_BITS) & 1 == 1
     }
 
     pub fn set(&mut self, x: usize, y: usize, value: bool) {
-        let idx = y * self.width + x;
-        if idx >= self.data.len() {
+        if x >= self.width || y >= self.height {
             return;
         }
-        self.data[idx] = value;
+        let bit = 1 << (WORD_BITS - 1 - x % WORD_BITS);
+        let word = &mut self.data[y * self.stride + x / WORD_BITS];
+        if value {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    pub fn line(&self, y: usize) -> impl Iterator<Item = bool> + '_ {
+        (0..self.width).map(move |x| self.get(x, y))
     }
 
-    pub fn full_cutout(&self) -> Cutout {
+    // the pixels of a line packed into bytes, black is 1 and the padding bits are 0
+    pub fn packed_line(&self, y: usize) -> Vec<u8> {
+        self.data[y * self.stride..(y + 1) * self.stride]
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .take(self.width.div_ceil(8))
+            .collect()
+    }
+
+    // the 64 pixels from x on, pixels outside of the image are 0
+    pub fn word(&self, x: usize, y: usize) -> u64 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        let line = &self.data[y * self.stride..(y + 1) * self.stride];
+        let idx = x / WORD_BITS;
+        let shift = x % WORD_BITS;
+        if shift == 0 {
+            line[idx]
+        } else {
+            line[idx] << shift
+                | line
+                    .get(idx + 1)
+                    .map_or(0, |word| word >> (WORD_BITS - shift))
+        }
+    }
+
+    // writes the pixels of word selected by mask from x on, pixels outside of the image are skipped
+    pub fn write_word(&mut self, x: usize, y: usize, word: u64, mask_bits: u64) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let mask_bits = mask_bits & mask(self.width - x);
+        let word = word & mask_bits;
+        let line = &mut self.data[y * self.stride..(y + 1) * self.stride];
+        let idx = x / WORD_BITS;
+        let shift = x % WORD_BITS;
+        line[idx] = line[idx] & !(mask_bits >> shift) | word >> shift;
+        if shift > 0 && idx + 1 < line.len() {
+            let shift = WORD_BITS - shift;
+            line[idx + 1] = line[idx + 1] & !(mask_bits << shift) | word << shift;
+        }
+    }
+
+    pub fn is_blank_area(&self, x: usize, y: usize, width: usize, height: usize) -> bool {
+        (y..y + height).all(|y| {
+            (x..x + width)
+                .step_by(WORD_BITS)
+                .all(|start| self.word(start, y) & mask(x + width - start) == 0)
+        })
+    }
+
+    pub fn fill_area(&mut self, x: usize, y: usize, width: usize, height: usize, value: bool) {
+        let word = if value { u64::MAX } else { 0 };
+        for y in y..y + height {
+            for start in (x..x + width).step_by(WORD_BITS) {
+                self.write_word(start, y, word, mask(x + width - start));
+            }
+        }
+    }
+
+    // the first x in x..end of line y with the given value
+    pub fn find(&self, x: usize, y: usize, end: usize, value: bool) -> Option<usize> {
+        (x..end).step_by(WORD_BITS).find_map(|start| {
+            let word = self.word(start, y);
+            let word = if value { word } else { !word } & mask(end - start);
+            (word != 0).then(|| start + word.leading_zeros() as usize)
+        })
+    }
+
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        let mut image = Self::new_empty(width, height);
+        for line in 0..height {
+            for start in (0..width).step_by(WORD_BITS) {
+                image.write_word(start, line, self.word(x + start, y + line), u64::MAX);
+            }
+        }
+        image
+    }
+
+    pub fn full_cutout(&self) -> Cutout<'_> {
         Cutout::new(self, self.width, self.height, 0, 0)
     }
 
@@ -193,7 +484,7 @@ impl Image {
         height: usize,
         offx: usize,
         offy: usize,
-    ) -> Result<Cutout, String> {
+    ) -> Result<Cutout<'_>, String> {
         if self.width < offx + width || self.height < offy + height {
             return Err("dimensions do not match".into());
         }
@@ -205,32 +496,40 @@ impl Image {
             let mut y = 0;
             while !self.get(x, y) {
                 self.set(x, y, true);
-                y = y + 1;
+                y += 1;
             }
             let mut y = self.height - 1;
             while !self.get(x, y) {
                 self.set(x, y, true);
-                y = y - 1;
+                y -= 1;
             }
         }
         for y in 0..self.height {
             let mut x = 0;
             while !self.get(x, y) {
                 self.set(x, y, true);
-                x = x + 1;
+                x += 1;
             }
             let mut x = self.width - 1;
             while !self.get(x, y) {
                 self.set(x, y, true);
-                x = x - 1;
+                x -= 1;
             }
         }
     }
 
+    // takes over the bounding boxes of the objects of the other image, so it replaces the black around them
+    pub fn merge(&mut self, other: &Image, connectivity: Connectivity) {
+        for object in Labels::new(other, true, connectivity).components() {
+            let part = other.crop(object.xmin, object.ymin, object.width(), object.height());
+            self.overwrite(&part, object.xmin, object.ymin);
+        }
+    }
+
     pub fn overwrite(&mut self, other: &Image, offx: usize, offy: usize) {
         for y in 0..other.height {
-            for x in 0..other.width {
-                self.set(x + offx, y + offy, other.get(x, y))
+            for x in (0..other.width).step_by(WORD_BITS) {
+                self.write_word(x + offx, y + offy, other.word(x, y), mask(other.width - x));
             }
         }
     }
@@ -256,10 +555,9 @@ impl Image {
     pub fn diff_down_up(&self) -> Self {
         let mut result = Self::new_empty(self.width, self.height);
         for y in 1..self.height {
-            for x in 0..self.width {
-                if !self.get(x, y) && self.get(x, y - 1) {
-                    result.set(x, y - 1, true)
-                }
+            for idx in 0..self.stride {
+                result.data[(y - 1) * self.stride + idx] =
+                    self.data[(y - 1) * self.stride + idx] & !self.data[y * self.stride + idx];
             }
         }
         result
@@ -283,30 +581,98 @@ impl Image {
                     result.set_pixels(
                         cutout
                             .pixels(false, true)
-                            .map(|pixel| pixel.subx((pixels_to_move * -1) as usize)),
+                            .map(|pixel| pixel.subx(-pixels_to_move as usize)),
                     );
                 }
-                return Ok(result);
+                return Ok(result.with_metadata(self.metadata.clone()));
             }
         }
-        Ok(Image::new_empty(width, self.height))
+        Ok(Image::new_empty(width, self.height).with_metadata(self.metadata.clone()))
     }
 
     pub fn clear_border_left(&mut self, pixels: usize) {
         let width = self.full_cutout().left_border(pixels).width();
-        for y in 0..self.height {
-            for x in 0..width {
-                self.set(x, y, false);
-            }
-        }
+        self.fill_area(0, 0, width, self.height, false);
     }
 
     pub fn clear_border_right(&mut self, pixels: usize) {
         let width = self.full_cutout().right_border(pixels).width();
-        for y in 0..self.height {
-            for x in (self.width - width)..self.width {
-                self.set(x, y, false);
-            }
+        self.fill_area(self.width - width, 0, width, self.height, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overwrite_across_words() {
+        let mut other = Image::new_empty(70, 2);
+        other.fill_area(0, 0, 70, 1, true);
+        other.set(69, 1, true);
+        let mut image = Image::new_empty(150, 3);
+        image.overwrite(&other, 60, 1);
+        assert!(!image.get(59, 1));
+        assert!(image.get(60, 1) && image.get(129, 1));
+        assert!(!image.get(130, 1));
+        assert_eq!(image.find(0, 2, 150, true), Some(129));
+        assert_eq!(
+            image.crop(60, 1, 70, 2).packed_line(1),
+            other.packed_line(1)
+        );
+        assert!(image.is_blank_area(0, 0, 150, 1));
+        assert!(!image.full_cutout().is_blank());
+    }
+
+    #[test]
+    fn from_bits_keeps_padding_clear() {
+        let image = Image::from_rows(&["#.#", ".##"]);
+        assert_eq!(image.packed_line(0), vec![0b10100000]);
+        assert_eq!(image.packed_line(1), vec![0b01100000]);
+        assert!(!image.get(3, 0));
+    }
+
+    #[test]
+    fn svg_path_per_object() {
+        // a ring, two pixels touching at a corner and a dot
+        let rows = ["###...#", "#.#.#..", "###..#."];
+        let image = Image::from_rows(&rows);
+        for (connectivity, count) in [(Connectivity::Four, 4), (Connectivity::Eight, 3)] {
+            let mut svg = Vec::new();
+            image.write_svg(&mut svg, 0.0, 0.0, connectivity).unwrap();
+            let paths = String::from_utf8(svg).unwrap().matches("<path").count();
+            let labels = Labels::new(&image, true, connectivity);
+            assert_eq!(labels.components().len(), count);
+            assert_eq!(paths, count);
         }
     }
+
+    #[test]
+    fn merge_takes_over_bounding_boxes() {
+        let other = Image::from_rows(&["..#....", ".###...", "..#...#"]);
+        let mut image = Image::new_empty(7, 3);
+        image.fill_area(0, 0, 7, 3, true);
+        image.merge(&other, Connectivity::Four);
+        assert!(image.get(0, 0) && image.get(5, 2));
+        assert!(!image.get(1, 0) && !image.get(3, 2));
+        assert!(image.get(2, 0) && image.get(6, 2));
+        assert!(image.get(4, 1) && image.get(6, 1));
+    }
+
+    #[test]
+    fn fill_holes_by_size_and_ratio() {
+        // a ring with a hole of 1 pixel and a frame with a hole of 4 pixels
+        let rows = ["###.####", "#.#.#..#", "###.#..#", "....####"];
+        let image = Image::from_rows(&rows);
+        let filled = image
+            .clone()
+            .fill_holes(4, f64::INFINITY, Connectivity::Four);
+        assert!(filled.get(1, 1) && filled.get(5, 1) && filled.get(6, 2));
+        let filled = image
+            .clone()
+            .fill_holes(3, f64::INFINITY, Connectivity::Four);
+        assert!(filled.get(1, 1) && !filled.get(5, 1));
+        let filled = image.fill_holes(4, 0.2, Connectivity::Four);
+        assert!(filled.get(1, 1) && !filled.get(5, 2));
+    }
 }
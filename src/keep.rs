@@ -0,0 +1,323 @@
+use crate::label::Component;
+use std::str::FromStr;
+
+const FIELDS: &str =
+    "size, width, height, aspect, density, x, y, xmin, xmax, ymin, ymax, holes or border";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Size,
+    Width,
+    Height,
+    // the longer side of the bounding box divided by the shorter one
+    Aspect,
+    // the share of the bounding box covered by the object
+    Density,
+    // the center of the bounding box
+    X,
+    Y,
+    Xmin,
+    Xmax,
+    Ymin,
+    Ymax,
+    Holes,
+    // 1 if the object touches the border of the image, 0 otherwise
+    Border,
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "size" => Ok(Self::Size),
+            "width" => Ok(Self::Width),
+            "height" => Ok(Self::Height),
+            "aspect" => Ok(Self::Aspect),
+            "density" => Ok(Self::Density),
+            "x" => Ok(Self::X),
+            "y" => Ok(Self::Y),
+            "xmin" => Ok(Self::Xmin),
+            "xmax" => Ok(Self::Xmax),
+            "ymin" => Ok(Self::Ymin),
+            "ymax" => Ok(Self::Ymax),
+            "holes" => Ok(Self::Holes),
+            "border" => Ok(Self::Border),
+            _ => Err(format!("expected {FIELDS}, got {value}")),
+        }
+    }
+}
+
+// what the fields of a keep expression are evaluated on
+pub struct Measures<'a> {
+    pub component: &'a Component,
+    pub holes: usize,
+    pub border: bool,
+}
+
+impl Field {
+    fn value(&self, measures: &Measures) -> f64 {
+        let component = measures.component;
+        let (width, height) = (component.width() as f64, component.height() as f64);
+        match self {
+            Self::Size => component.size as f64,
+            Self::Width => width,
+            Self::Height => height,
+            Self::Aspect => width.max(height) / width.min(height),
+            Self::Density => component.size as f64 / (width * height),
+            Self::X => (component.xmin + component.xmax) as f64 / 2.0,
+            Self::Y => (component.ymin + component.ymax) as f64 / 2.0,
+            Self::Xmin => component.xmin as f64,
+            Self::Xmax => component.xmax as f64,
+            Self::Ymin => component.ymin as f64,
+            Self::Ymax => component.ymax as f64,
+            Self::Holes => measures.holes as f64,
+            Self::Border => measures.border as u8 as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Factor {
+    Number(f64),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Compare {
+    fn matches(&self, a: f64, b: f64) -> bool {
+        match self {
+            Self::Less => a < b,
+            Self::LessEqual => a <= b,
+            Self::Greater => a > b,
+            Self::GreaterEqual => a >= b,
+            Self::Equal => a == b,
+            Self::NotEqual => a != b,
+        }
+    }
+}
+
+// a rule which objects to keep, like size>=50 && aspect<8 or !(height>3*width || border)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Keep {
+    Or(Vec<Keep>),
+    And(Vec<Keep>),
+    Not(Box<Keep>),
+    // products of numbers and fields, a product alone holds when it isn't 0
+    Compare(Vec<Factor>, Compare, Vec<Factor>),
+}
+
+impl Keep {
+    pub fn matches(&self, measures: &Measures) -> bool {
+        let product = |factors: &[Factor]| {
+            factors
+                .iter()
+                .map(|factor| match factor {
+                    Factor::Number(number) => *number,
+                    Factor::Field(field) => field.value(measures),
+                })
+                .product::<f64>()
+        };
+        match self {
+            Self::Or(rules) => rules.iter().any(|rule| rule.matches(measures)),
+            Self::And(rules) => rules.iter().all(|rule| rule.matches(measures)),
+            Self::Not(rule) => !rule.matches(measures),
+            Self::Compare(a, compare, b) => compare.matches(product(a), product(b)),
+        }
+    }
+
+    // whether the rule needs the holes of the objects, which take an extra labeling to count
+    pub fn uses_holes(&self) -> bool {
+        match self {
+            Self::Or(rules) | Self::And(rules) => rules.iter().any(Self::uses_holes),
+            Self::Not(rule) => rule.uses_holes(),
+            Self::Compare(a, _, b) => a
+                .iter()
+                .chain(b)
+                .any(|factor| *factor == Factor::Field(Field::Holes)),
+        }
+    }
+}
+
+impl FromStr for Keep {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(value)?,
+            position: 0,
+        };
+        let keep = parser.or()?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(keep),
+            Some(token) => Err(format!("unexpected {token} in {value}")),
+        }
+    }
+}
+
+fn tokenize(value: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut token = c.to_string();
+        if c.is_whitespace() {
+            continue;
+        } else if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            while let Some(c) =
+                chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '_')
+            {
+                token.push(c);
+            }
+        } else if "<>=!&|".contains(c) {
+            if let Some(c) = chars.next_if(|c| "=&|".contains(*c)) {
+                token.push(c);
+            }
+            if ["=", "&", "|"].contains(&token.as_str()) {
+                return Err(format!("expected {token}{token} in {value}"));
+            }
+        } else if !"()*".contains(c) {
+            return Err(format!("unexpected {c} in {value}"));
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+// recursive descent, || binds weaker than &&, which binds weaker than ! and comparisons
+struct Parser {
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl Parser {
+    fn next_if(&mut self, token: &str) -> bool {
+        let matches = self.tokens.get(self.position).is_some_and(|t| t == token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn next(&mut self) -> Result<&str, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or("unexpected end of the expression")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn or(&mut self) -> Result<Keep, String> {
+        let mut rules = vec![self.and()?];
+        while self.next_if("||") {
+            rules.push(self.and()?);
+        }
+        Ok(if rules.len() == 1 {
+            rules.remove(0)
+        } else {
+            Keep::Or(rules)
+        })
+    }
+
+    fn and(&mut self) -> Result<Keep, String> {
+        let mut rules = vec![self.unary()?];
+        while self.next_if("&&") {
+            rules.push(self.unary()?);
+        }
+        Ok(if rules.len() == 1 {
+            rules.remove(0)
+        } else {
+            Keep::And(rules)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Keep, String> {
+        if self.next_if("!") {
+            return Ok(Keep::Not(Box::new(self.unary()?)));
+        }
+        if self.next_if("(") {
+            let rule = self.or()?;
+            return match self.next()? {
+                ")" => Ok(rule),
+                token => Err(format!("expected ), got {token}")),
+            };
+        }
+        let a = self.product()?;
+        let compare = match self.tokens.get(self.position).map(String::as_str) {
+            Some("<") => Compare::Less,
+            Some("<=") => Compare::LessEqual,
+            Some(">") => Compare::Greater,
+            Some(">=") => Compare::GreaterEqual,
+            Some("==") => Compare::Equal,
+            Some("!=") => Compare::NotEqual,
+            _ => {
+                return Ok(Keep::Compare(
+                    a,
+                    Compare::NotEqual,
+                    vec![Factor::Number(0.0)],
+                ))
+            }
+        };
+        self.position += 1;
+        Ok(Keep::Compare(a, compare, self.product()?))
+    }
+
+    fn product(&mut self) -> Result<Vec<Factor>, String> {
+        let mut factors = vec![self.factor()?];
+        while self.next_if("*") {
+            factors.push(self.factor()?);
+        }
+        Ok(factors)
+    }
+
+    fn factor(&mut self) -> Result<Factor, String> {
+        let token = self.next()?;
+        if token.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            token
+                .parse()
+                .map(Factor::Number)
+                .map_err(|_| format!("expected a number, got {token}"))
+        } else {
+            token.parse().map(Factor::Field)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_expressions() {
+        let speck = Component {
+            size: 6,
+            xmin: 10,
+            xmax: 10,
+            ymin: 0,
+            ymax: 5,
+        };
+        let measures = Measures {
+            component: &speck,
+            holes: 0,
+            border: true,
+        };
+        let matches = |rule: &str| rule.parse::<Keep>().unwrap().matches(&measures);
+        assert!(matches("size>=5 && aspect<8"));
+        assert!(!matches("size >= 50 || width > 1"));
+        assert!(!matches("!(height>3*width || border)"));
+        assert!(matches("density==1 && holes==0 && x<=10.5"));
+        assert!("size>=".parse::<Keep>().is_err());
+        assert!("size=5".parse::<Keep>().is_err());
+        assert!("area>5".parse::<Keep>().is_err());
+        assert!("(size>5".parse::<Keep>().is_err());
+    }
+}
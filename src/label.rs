@@ -0,0 +1,529 @@
+use crate::image::Image;
+use crate::keep::{Keep, Measures};
+use crate::object::{Pixel, SimpleObject};
+use clap::ValueEnum;
+use std::error::Error;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Connectivity {
+    /// Pixels touch at their edges
+    #[value(name = "4")]
+    Four,
+    /// Pixels touch at their edges or corners
+    #[value(name = "8")]
+    Eight,
+}
+
+impl Connectivity {
+    // the connectivity of the background, so that it can't cross a line of the objects
+    pub fn opposite(&self) -> Self {
+        match self {
+            Self::Four => Self::Eight,
+            Self::Eight => Self::Four,
+        }
+    }
+
+    pub fn offsets(&self) -> &'static [(isize, isize)] {
+        match self {
+            Self::Four => &[(1, 0), (0, 1), (-1, 0), (0, -1)],
+            Self::Eight => &[
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (-1, 1),
+                (-1, 0),
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+            ],
+        }
+    }
+
+    // how far runs of neighboring rows may lie apart and still touch
+    fn reach(&self) -> usize {
+        match self {
+            Self::Four => 0,
+            Self::Eight => 1,
+        }
+    }
+}
+
+// the bounding box and pixel count of a connected component
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    pub size: usize,
+    pub xmin: usize,
+    pub xmax: usize,
+    pub ymin: usize,
+    pub ymax: usize,
+}
+
+impl Component {
+    pub fn width(&self) -> usize {
+        self.xmax - self.xmin + 1
+    }
+
+    pub fn height(&self) -> usize {
+        self.ymax - self.ymin + 1
+    }
+
+    pub fn touches_border(&self, width: usize, height: usize) -> bool {
+        self.xmin == 0 || self.ymin == 0 || self.xmax == width - 1 || self.ymax == height - 1
+    }
+
+    // whether the other component lies inside the bounding box without touching its edges
+    pub fn surrounds(&self, other: &Component) -> bool {
+        self.xmin < other.xmin
+            && self.ymin < other.ymin
+            && self.xmax > other.xmax
+            && self.ymax > other.ymax
+    }
+}
+
+// the connected components of the pixels of one color, numbered in the order of their first pixel
+pub struct Labels {
+    width: usize,
+    height: usize,
+    connectivity: Connectivity,
+    labels: Vec<u32>,
+    components: Vec<Component>,
+}
+
+impl Labels {
+    pub fn new(image: &Image, value: bool, connectivity: Connectivity) -> Self {
+        let width = image.width();
+        let height = image.height();
+
+        // first pass: the runs of every row get the label of the runs above they overlap
+        let mut parents = Vec::new();
+        let mut runs: Vec<(usize, usize, usize, usize)> = Vec::new();
+        let mut previous = 0..0;
+        for y in 0..height {
+            let first = runs.len();
+            let mut above = previous.start;
+            let mut x = 0;
+            while let Some(start) = image.find(x, y, width, value) {
+                let end = image.find(start, y, width, !value).unwrap_or(width);
+                while above < previous.end && runs[above].2 + connectivity.reach() <= start {
+                    above += 1;
+                }
+                let mut label = None;
+                for run in runs[above..previous.end]
+                    .iter()
+                    .take_while(|run| run.1 < end + connectivity.reach())
+                {
+                    label = Some(match label {
+                        Some(label) => union(&mut parents, label, run.3),
+                        None => find(&mut parents, run.3),
+                    });
+                }
+                let label = label.unwrap_or_else(|| {
+                    parents.push(parents.len());
+                    parents.len() - 1
+                });
+                runs.push((y, start, end, label));
+                x = end;
+            }
+            previous = first..runs.len();
+        }
+
+        // second pass: the runs get the final labels of their sets
+        let mut numbers = vec![0; parents.len()];
+        let mut components: Vec<Component> = Vec::new();
+        let mut labels = vec![0; width * height];
+        for (y, start, end, label) in runs {
+            let root = find(&mut parents, label);
+            if numbers[root] == 0 {
+                components.push(Component {
+                    size: 0,
+                    xmin: start,
+                    xmax: end - 1,
+                    ymin: y,
+                    ymax: y,
+                });
+                numbers[root] = components.len() as u32;
+            }
+            let component = &mut components[numbers[root] as usize - 1];
+            component.size += end - start;
+            component.xmin = component.xmin.min(start);
+            component.xmax = component.xmax.max(end - 1);
+            component.ymax = y;
+            labels[y * width + start..y * width + end].fill(numbers[root]);
+        }
+
+        Self {
+            width,
+            height,
+            connectivity,
+            labels,
+            components,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // the index of the component of a pixel, none for the other color and outside of the image
+    pub fn label(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        match self.labels[y * self.width + x] {
+            0 => None,
+            label => Some(label as usize - 1),
+        }
+    }
+
+    pub fn components(&self) -> &[Component] {
+        &self.components
+    }
+
+    pub fn object(&self, idx: usize) -> SimpleObject {
+        let component = &self.components[idx];
+        (component.ymin..=component.ymax)
+            .flat_map(|y| (component.xmin..=component.xmax).map(move |x| (x, y)))
+            .filter(|(x, y)| self.label(*x, *y) == Some(idx))
+            .map(|(x, y)| Pixel::new(x, y))
+            .collect()
+    }
+
+    // the components of the other labels that touch the component
+    pub fn neighbors(&self, idx: usize, other: &Labels) -> Vec<usize> {
+        let component = &self.components[idx];
+        let mut neighbors = Vec::new();
+        for y in component.ymin..=component.ymax {
+            for x in component.xmin..=component.xmax {
+                if self.label(x, y) != Some(idx) {
+                    continue;
+                }
+                for (dx, dy) in self.connectivity.offsets() {
+                    let x = x.wrapping_add_signed(*dx);
+                    let y = y.wrapping_add_signed(*dy);
+                    if let Some(label) = other.label(x, y) {
+                        if !neighbors.contains(&label) {
+                            neighbors.push(label);
+                        }
+                    }
+                }
+            }
+        }
+        neighbors
+    }
+}
+
+// the objects that filter_objects keeps and the holes it fills
+pub struct ObjectFilter {
+    objects: Labels,
+    kept: Vec<bool>,
+    holes: Labels,
+    filled: Vec<bool>,
+}
+
+impl ObjectFilter {
+    pub fn new(
+        image: &Image,
+        tobj: usize,
+        clean_border: bool,
+        connectivity: Connectivity,
+        keep: Option<&Keep>,
+    ) -> Self {
+        let (width, height) = (image.width(), image.height());
+        let objects = Labels::new(image, true, connectivity);
+        let holes = Labels::new(image, false, connectivity.opposite());
+
+        // every hole inside the image belongs to the one object next to it that surrounds it
+        let mut hole_counts = vec![0; objects.components().len()];
+        if keep.is_some_and(Keep::uses_holes) {
+            for (idx, hole) in holes.components().iter().enumerate() {
+                if hole.touches_border(width, height) {
+                    continue;
+                }
+                if let Some(object) = parent(&objects, &holes, idx) {
+                    hole_counts[object] += 1;
+                }
+            }
+        }
+
+        let kept = objects
+            .components()
+            .iter()
+            .zip(hole_counts)
+            .map(|(object, holes)| {
+                let border = object.touches_border(width, height);
+                (tobj == 0 || (object.size >= tobj && (!clean_border || !border)))
+                    && keep.is_none_or(|keep| {
+                        keep.matches(&Measures {
+                            component: object,
+                            holes,
+                            border,
+                        })
+                    })
+            })
+            .collect::<Vec<bool>>();
+        let filled = vec![false; holes.components().len()];
+        Self {
+            objects,
+            kept,
+            holes,
+            filled,
+        }
+    }
+
+    // fills the holes of kept objects up to max_size pixels and max_ratio times the size of the object,
+    // the holes are taken after the removal, so that removed objects don't split them
+    pub fn fill_holes(mut self, max_size: usize, max_ratio: f64) -> Self {
+        self.holes = Labels::new(&self.to_image(), false, self.holes.connectivity);
+        self.filled = self
+            .holes
+            .components()
+            .iter()
+            .enumerate()
+            .map(|(idx, hole)| {
+                hole.size <= max_size
+                    && parent(&self.objects, &self.holes, idx).is_some_and(|object| {
+                        self.kept[object]
+                            && hole.size as f64
+                                <= max_ratio * self.objects.components()[object].size as f64
+                    })
+            })
+            .collect();
+        self
+    }
+
+    pub fn to_image(&self) -> Image {
+        let bits = (0..self.objects.height).flat_map(|y| {
+            (0..self.objects.width).map(move |x| {
+                self.objects
+                    .label(x, y)
+                    .is_some_and(|object| self.kept[object])
+                    || self.holes.label(x, y).is_some_and(|hole| self.filled[hole])
+            })
+        });
+        Image::from_bits(self.objects.width, self.objects.height, bits)
+    }
+
+    // an rgb png with a color per kept object, removed objects hatched red and filled holes blue,
+    // everything but white and the hatching is black in to_image
+    pub fn write_png(&self, writer: impl Write) -> Result<(), Box<dyn Error>> {
+        let (width, height) = (self.objects.width, self.objects.height);
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut data = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let filled = self.holes.label(x, y).is_some_and(|hole| self.filled[hole]);
+                let rgb = match self.objects.label(x, y) {
+                    Some(object) if self.kept[object] => label_color(object),
+                    _ if filled => [0, 0, 255],
+                    Some(_) if (x + y) % 4 < 2 => [255, 0, 0],
+                    Some(_) => [255, 170, 170],
+                    None => [255, 255, 255],
+                };
+                data.extend(rgb);
+            }
+        }
+        encoder.write_header()?.write_image_data(&data)?;
+        Ok(())
+    }
+}
+
+// well spread hues by the golden ratio, dark enough to stand out from the white background
+fn label_color(label: usize) -> [u8; 3] {
+    let hue = (label as f64 * 0.618_033_988_75).fract() * 6.0;
+    let (value, saturation) = (0.75, 0.8);
+    let f = |n: f64| {
+        let k = (n + hue) % 6.0;
+        let channel = value - value * saturation * k.min(4.0 - k).clamp(0.0, 1.0);
+        (channel * 255.0).round() as u8
+    };
+    [f(5.0), f(3.0), f(1.0)]
+}
+
+// the object around a hole, the one next to it that surrounds it
+fn parent(objects: &Labels, holes: &Labels, hole: usize) -> Option<usize> {
+    let component = &holes.components()[hole];
+    if component.touches_border(holes.width, holes.height) {
+        return None;
+    }
+    holes
+        .neighbors(hole, objects)
+        .into_iter()
+        .find(|object| objects.components()[*object].surrounds(component))
+}
+
+fn find(parents: &mut [usize], label: usize) -> usize {
+    let mut root = label;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut label = label;
+    while parents[label] != root {
+        let next = parents[label];
+        parents[label] = root;
+        label = next;
+    }
+    root
+}
+
+// joins both sets under the lower root
+fn union(parents: &mut [usize], a: usize, b: usize) -> usize {
+    let a = find(parents, a);
+    let b = find(parents, b);
+    let (root, child) = if a < b { (a, b) } else { (b, a) };
+    parents[child] = root;
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cutout::Cutout;
+    use crate::object::Object;
+
+    #[test]
+    fn labels_join_at_the_bottom() {
+        // a u whose arms only meet in the last row, a dot and a diagonal neighbor of the dot
+        let rows = ["#.#..#", "#.#.#.", "###...", "......"];
+        let image = Image::from_rows(&rows);
+        let labels = Labels::new(&image, true, Connectivity::Four);
+        assert_eq!(labels.components().len(), 3);
+        assert_eq!(
+            labels.components()[0],
+            Component {
+                size: 7,
+                xmin: 0,
+                xmax: 2,
+                ymin: 0,
+                ymax: 2,
+            }
+        );
+        assert_eq!(labels.label(2, 0), Some(0));
+        assert_eq!(labels.label(5, 0), Some(1));
+        assert_eq!(labels.label(4, 1), Some(2));
+        assert_eq!(labels.label(1, 1), None);
+
+        let holes = Labels::new(&image, false, Connectivity::Eight);
+        assert_eq!(holes.label(1, 0), Some(0));
+        assert_eq!(holes.components()[0].size, 2);
+        assert_eq!(holes.neighbors(0, &labels), vec![0]);
+
+        let labels = Labels::new(&image, true, Connectivity::Eight);
+        assert_eq!(labels.components().len(), 2);
+        assert_eq!(labels.label(4, 1), Some(1));
+    }
+
+    #[test]
+    fn debug_image_matches_the_filtered_image() {
+        // a ring with a speck in its hole and a frame with a tail
+        let rows = [
+            "#####.......", //
+            "#...#.#####.",
+            "#.#.#.#...#.",
+            "#...#.#...#.",
+            "#####.#####.",
+            "..........#.",
+        ];
+        let image = Image::from_rows(&rows);
+        // the hole of the ring has 9 pixels once the speck in it is removed
+        let filter = ObjectFilter::new(&image, 3, false, Connectivity::Four, None);
+        let filtered = filter.fill_holes(8, 1.0).to_image();
+        assert!(!filtered.get(2, 2) && !filtered.get(1, 1) && filtered.get(7, 2));
+        let filter =
+            ObjectFilter::new(&image, 3, false, Connectivity::Four, None).fill_holes(9, 1.0);
+        let filtered = filter.to_image();
+        assert!(filtered.get(2, 2) && filtered.get(1, 1) && filtered.get(7, 2));
+
+        let mut png = Vec::new();
+        filter.write_png(&mut png).unwrap();
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        for (idx, rgb) in data.chunks(3).enumerate() {
+            let black = !matches!(rgb, [255, 255, 255] | [255, 0, 0] | [255, 170, 170]);
+            assert_eq!(black, filtered.get(idx % 12, idx / 12), "{idx} {rgb:?}");
+        }
+        assert_eq!(&data[(2 * 12 + 2) * 3..][..3], &[0, 0, 255]);
+    }
+
+    #[test]
+    fn labels_match_flood_fill_on_noise() {
+        let (width, height) = (97, 61);
+        let mut state = 12345u32;
+        let image = Image::from_bits(
+            width,
+            height,
+            (0..width * height).map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                state >> 16 & 3 != 0
+            }),
+        );
+        for connectivity in [Connectivity::Four, Connectivity::Eight] {
+            let labels = Labels::new(&image, true, connectivity);
+            let mut seen = vec![false; width * height];
+            let mut count = 0;
+            for start in 0..width * height {
+                if seen[start] || !image.get(start % width, start / width) {
+                    continue;
+                }
+                let label = labels.label(start % width, start / width).unwrap();
+                let mut stack = vec![(start % width, start / width)];
+                seen[start] = true;
+                let mut size = 0;
+                while let Some((x, y)) = stack.pop() {
+                    assert_eq!(labels.label(x, y), Some(label));
+                    size += 1;
+                    for (dx, dy) in connectivity.offsets() {
+                        let (x, y) = (x.wrapping_add_signed(*dx), y.wrapping_add_signed(*dy));
+                        if image.get(x, y) && !seen[y * width + x] {
+                            seen[y * width + x] = true;
+                            stack.push((x, y));
+                        }
+                    }
+                }
+                assert_eq!(labels.components()[label].size, size);
+                count += 1;
+            }
+            assert_eq!(labels.components().len(), count);
+        }
+    }
+
+    #[test]
+    fn cutout_objects_match_labels() {
+        let (width, height) = (53, 41);
+        let mut state = 777u32;
+        let image = Image::from_bits(
+            width,
+            height,
+            (0..width * height).map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                state >> 16 & 3 == 0
+            }),
+        );
+        let cutout = Cutout::new(&image, 40, 30, 7, 5);
+        for invert in [false, true] {
+            for connectivity in [Connectivity::Four, Connectivity::Eight] {
+                let labels = Labels::new(&cutout.to_image(), !invert, connectivity);
+                let objects = cutout.objects(invert, connectivity);
+                assert_eq!(objects.len(), labels.components().len());
+                for (object, component) in objects.iter().zip(labels.components()) {
+                    assert_eq!(object.size(), component.size);
+                    assert_eq!(
+                        (object.xmin(), object.ymin()),
+                        (component.xmin + 7, component.ymin + 5)
+                    );
+                    assert_eq!(
+                        (object.xmax(), object.ymax()),
+                        (component.xmax + 7, component.ymax + 5)
+                    );
+                }
+            }
+        }
+    }
+}
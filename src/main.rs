@@ -1,26 +1,75 @@
+pub mod background;
+pub mod cluster;
+pub mod color;
+pub mod contour;
+pub mod convert;
 pub mod cutout;
 pub mod decoder;
+pub mod dither;
 pub mod encoder;
+pub mod gray;
 pub mod image;
+pub mod keep;
+pub mod label;
+pub mod metadata;
+pub mod netpbm;
 pub mod object;
+pub mod pdf;
+pub mod svg;
+pub mod threshold;
+pub mod tiff;
 
-use clap::Parser;
+use background::Flatten;
+use clap::{Parser, Subcommand, ValueEnum};
+use cluster::{kmeans, Cluster, ColorHistogram};
+use color::{hsv, ColorMode, ExtraColor, Metric};
+use convert::Luminance;
+use decoder::DecodeOptions;
+use dither::Dither;
+use gray::{read_png, read_pnm, GrayImage};
 use image::Image;
+use keep::Keep;
+use label::{Connectivity, Labels, ObjectFilter};
 use object::Object;
+use pdf::{encode_pdf, Paper};
+use std::error::Error;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use threshold::Threshold;
+use tiff::{encode_tiff, Compression};
 
 #[derive(Debug, Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    /// The input path of the png
-    pub input_path: String,
-    /// The output path of the png
-    pub output_path: String,
-    /// The threshold that defines when a pixel gets set to 0 or 1 (its a value between 0 and 1)
-    #[arg(short, long, default_value_t = 0.5)] // 0.8
-    pub threshold: f64,
-    /// The threshold of the pixel size of objects that are filter out as salt and pepper
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// The input paths of the png or netpbm (pbm, pgm, ppm, pnm, pam) pages, - reads from stdin, followed by the output path of the png, pbm, svg, tiff or pdf, the format is chosen by the extension (only tiff and pdf take several pages), - writes to stdout
+    #[arg(required = true, num_args = 1.., value_name = "PATHS")]
+    pub paths: Vec<String>,
+    #[command(flatten)]
+    pub read: ReadArgs,
+    /// The output format, overrides the extension of the output path (png when writing to stdout)
+    #[arg(long, value_enum)]
+    pub format: Option<Format>,
+    /// The threshold of the pixel size of objects that are filter out as salt and pepper (holes are kept, see --fill-holes)
     #[arg(short, long, default_value_t = 0)] // 50
     pub obj_threshold: usize,
+    /// Only keep the objects matching this rule on size, width, height, aspect, density, x, y, xmin, xmax, ymin, ymax, holes and border, combined with && || ! and (), e.g. 'size>=50 && aspect<8'
+    #[arg(long)]
+    pub keep: Option<Keep>,
+    /// Fill the holes of objects up to this many pixels, like the counters of letters
+    #[arg(long)]
+    pub fill_holes: Option<usize>,
+    /// Only fill holes up to this many times the pixel size of the object around them
+    #[arg(long, requires = "fill_holes")]
+    pub fill_ratio: Option<f64>,
     /// The threshold to read in a specific coller
     #[arg(short, long, default_value_t = 0.0)] // 0.2
     pub color_threshold: f64,
@@ -33,6 +82,15 @@ struct Args {
     /// The blue channel of the extra collor to read in
     #[arg(short, long, default_value_t = 0)] // 13
     pub blue: u8,
+    /// An extra color to read in as r,g,b:threshold[:mode] with the mode merge (default), erase or keep-separate, can be repeated
+    #[arg(long = "color")]
+    pub extra_colors: Vec<ExtraColor>,
+    /// How the distance to the extra colors is measured (lab metrics divide delta e by 100)
+    #[arg(long, value_enum, default_value_t = Metric::Rgb)]
+    pub metric: Metric,
+    /// The hue (degrees), saturation and value (0 to 1) differences that make up the distance 1 of the hsv metric
+    #[arg(long, value_parser = parse_hsv, default_value = "180,1,1")]
+    pub hsv_range: [f64; 3],
     /// The width to which the image should be padded
     #[arg(short, long, default_value_t = 0)] // 2480
     pub width: usize,
@@ -42,59 +100,684 @@ struct Args {
     /// The max width of the right border to clear
     #[arg(long, default_value_t = 0)]
     pub right_border: usize,
+    /// Write an rgb png of the objects of the black pixels, with removed objects hatched red and filled holes blue (numbered -1, -2, ... for several input pages)
+    #[arg(long)]
+    pub debug_labels: Option<String>,
+    /// The compression of tiff and pdf output
+    #[arg(long, value_enum, default_value_t = Compression::Group4)]
+    pub compression: Compression,
+    /// The resolution written to the output in dots per inch (by default the resolution of the input or 300)
+    #[arg(long)]
+    pub dpi: Option<f64>,
+    /// The paper size pdf pages are padded onto (by default the page has the size of the image)
+    #[arg(long, value_enum)]
+    pub paper: Option<Paper>,
+    /// The max distance in pixels the outlines of svg output may deviate from the pixel edges
+    #[arg(long, default_value_t = 1.0)]
+    pub simplify: f64,
+    /// How much svg outlines get rounded into curves (0 keeps straight lines, 1 is a full Catmull-Rom curve)
+    #[arg(long, default_value_t = 0.0)]
+    pub smooth: f64,
 }
 
-fn main() {
-    let args = Args::parse();
-
-    let mut image_black = Image::from_png_filter(
-        args.input_path.clone(),
-        0.0,
-        0.0,
-        0.0,
-        args.threshold,
-        args.obj_threshold,
-        true,
-    )
-    .unwrap();
+// the options of reading in the black pixels, shared by the objects subcommand
+#[derive(Debug, clap::Args)]
+struct ReadArgs {
+    /// The threshold that defines when a pixel gets set to 0 or 1 (its a value between 0 and 1), auto (otsu) and kittler to pick it from the histogram, or sauvola, niblack and bradley to pick it per pixel from its window
+    #[arg(short, long, default_value = "0.5")] // 0.8
+    pub threshold: Threshold,
+    /// Also read in the pixels below this weaker threshold that connect to pixels below the threshold (hysteresis)
+    #[arg(long, conflicts_with = "dither")]
+    pub weak_threshold: Option<f64>,
+    /// Dither the grays instead of cutting them off at the threshold, which then is the gray that gets half of the pixels black
+    #[arg(long, value_enum)]
+    pub dither: Option<Dither>,
+    /// Scan every other row of error diffusion backwards
+    #[arg(long, requires = "dither")]
+    pub serpentine: bool,
+    /// The width and height in pixels of the window of local thresholds
+    #[arg(long, default_value_t = 31)]
+    pub window: usize,
+    /// The sensitivity of local thresholds (by default 0.2 for sauvola and niblack, 0.15 for bradley)
+    #[arg(long)]
+    pub k: Option<f64>,
+    /// Whether pixels that only touch at their corners belong to the same object (the background and holes use the other one)
+    #[arg(long, value_enum, default_value_t = Connectivity::Four)]
+    pub connectivity: Connectivity,
+    /// Convert the input to grayscale with these weights before reading in black (replaces convert.py)
+    #[arg(long, value_enum)] // rec601
+    pub luminance: Option<Luminance>,
+    /// The number of gray levels the converted input gets quantized to
+    #[arg(long, requires = "luminance")] // 256
+    pub colors: Option<usize>,
+    /// The color transparent pixels are composited onto before reading in (r,g,b)
+    #[arg(long, value_parser = parse_rgb, default_value = "255,255,255")]
+    pub background: [u8; 3],
+    /// Divide out the brightness of the paper background before reading in, keeping the hues: closing[:kernel size in pixels] or polynomial[:degree]
+    #[arg(long)]
+    pub flatten: Option<Flatten>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the dominant colors of an image and suggest extra color parameters to isolate them
+    Colors {
+        /// The input path of the png or netpbm image, - reads from stdin
+        input_path: String,
+        /// The number of color clusters
+        #[arg(short, long, default_value_t = 6)]
+        clusters: usize,
+        /// The color transparent pixels are composited onto (r,g,b)
+        #[arg(long, value_parser = parse_rgb, default_value = "255,255,255")]
+        background: [u8; 3],
+    },
+    /// Print the bounding box, size and shape measures of every object of the black pixels, the perimeter counts the pixel edges between the object and the background
+    Objects {
+        /// The input path of the png or netpbm image, - reads from stdin
+        input_path: String,
+        #[command(flatten)]
+        read: ReadArgs,
+        /// The output format
+        #[arg(long, value_enum, default_value_t = Table::Json)]
+        format: Table,
+    },
+}
+
+impl Args {
+    // the output path is the last one, so that options can also come between the paths
+    fn split_paths(&self) -> Result<(&str, &[String]), Box<dyn Error>> {
+        match self.paths.split_last() {
+            Some((output_path, input_paths)) if !input_paths.is_empty() => {
+                Ok((output_path, input_paths))
+            }
+            _ => Err("expected the input paths followed by the output path".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Table {
+    /// An array of json objects, one per line
+    Json,
+    /// Comma separated values with a header line
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// 1-bit grayscale png
+    Png,
+    /// Binary netpbm bitmap
+    Pbm,
+    /// Traced outlines of the objects
+    Svg,
+    /// Multi-page tiff
+    Tiff,
+    /// Multi-page pdf
+    Pdf,
+}
+
+fn parse_rgb(value: &str) -> Result<[u8; 3], String> {
+    let channels = value
+        .split(',')
+        .map(|channel| channel.trim().parse::<u8>().map_err(|err| err.to_string()))
+        .collect::<Result<Vec<u8>, String>>()?;
+    channels
+        .try_into()
+        .map_err(|_| format!("expected three comma separated channels, got {value}"))
+}
+
+fn parse_hsv(value: &str) -> Result<[f64; 3], String> {
+    let ranges = value
+        .split(',')
+        .map(|range| range.trim().parse::<f64>().map_err(|err| err.to_string()))
+        .collect::<Result<Vec<f64>, String>>()?;
+    ranges
+        .try_into()
+        .map_err(|_| format!("expected three comma separated ranges, got {value}"))
+}
+
+fn extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+// debug.png gets debug-2.png for the second page
+fn page_path(path: &str, page: usize) -> String {
+    let path = Path::new(path);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let name = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => format!("{stem}-{page}.{extension}"),
+        None => format!("{stem}-{page}"),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+fn read_input(path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if path == "-" {
+        let mut data = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut data)?;
+        Ok(data)
+    } else {
+        Ok(std::fs::read(path)?)
+    }
+}
+
+fn is_netpbm(path: &str, data: &[u8]) -> bool {
+    if path == "-" {
+        data.starts_with(b"P")
+    } else {
+        matches!(
+            extension(path).as_str(),
+            "pbm" | "pgm" | "ppm" | "pnm" | "pam"
+        )
+    }
+}
+
+fn read_image(path: &str, data: &[u8], options: &DecodeOptions) -> Result<Image, Box<dyn Error>> {
+    if is_netpbm(path, data) {
+        Image::from_pnm_reader(data, options)
+    } else {
+        Image::from_png_reader(data, options)
+    }
+}
+
+fn read_gray(
+    path: &str,
+    data: &[u8],
+    options: &DecodeOptions,
+) -> Result<GrayImage, Box<dyn Error>> {
+    if is_netpbm(path, data) {
+        GrayImage::from_pnm_reader(data, options)
+    } else {
+        GrayImage::from_png_reader(data, options)
+    }
+}
+
+fn output_format(path: &str, args: &Args) -> Format {
+    if let Some(format) = args.format {
+        return format;
+    }
+    match extension(path).as_str() {
+        "tif" | "tiff" => Format::Tiff,
+        "pdf" => Format::Pdf,
+        "pbm" => Format::Pbm,
+        "svg" => Format::Svg,
+        _ => Format::Png,
+    }
+}
+
+fn write_images(images: &[Image], path: &str, args: &Args) -> Result<(), Box<dyn Error>> {
+    let format = output_format(path, args);
+    if images.len() > 1 && format != Format::Tiff && format != Format::Pdf {
+        return Err(
+            "several pages (input pages or separate colors) need a tiff or pdf output".into(),
+        );
+    }
+    let mut writer: Box<dyn Write> = if path == "-" {
+        Box::new(std::io::stdout().lock())
+    } else {
+        Box::new(BufWriter::new(std::fs::File::create(path)?))
+    };
+    write_output(images, format, &mut writer, args)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_output(
+    images: &[Image],
+    format: Format,
+    mut writer: impl Write,
+    args: &Args,
+) -> Result<(), Box<dyn Error>> {
+    let pages = images.iter().collect::<Vec<&Image>>();
+    match format {
+        Format::Tiff => writer.write_all(&encode_tiff(&pages, args.compression))?,
+        Format::Pdf => writer.write_all(&encode_pdf(&pages, args.compression, args.paper))?,
+        Format::Pbm => images[0].write_pbm(&mut writer, false)?,
+        Format::Svg => images[0].write_svg(
+            &mut writer,
+            args.simplify,
+            args.smooth,
+            args.read.connectivity,
+        )?,
+        Format::Png => images[0].write_png(&mut writer)?,
+    }
+    Ok(())
+}
+
+// the weak threshold takes in more pixels than the threshold, a stricter one would drop strokes
+fn hysteresis(
+    gray: &GrayImage,
+    threshold: f64,
+    weak: f64,
+    connectivity: Connectivity,
+) -> Result<Image, Box<dyn Error>> {
+    if weak < threshold {
+        return Err(format!(
+            "the weak threshold {weak} must not be below the threshold {threshold}"
+        )
+        .into());
+    }
+    Ok(gray.hysteresis(threshold, weak, connectivity))
+}
+
+// reads in the black pixels with the threshold, dithering or hysteresis of the options
+fn read_black(path: &str, data: &[u8], read: &ReadArgs) -> Result<Image, Box<dyn Error>> {
+    let mut options = DecodeOptions::new(0.0, 0.0, 0.0, 0.0);
+    options.luminance = read.luminance;
+    options.colors = read.colors;
+    options.background = read.background.map(|channel| channel as f64);
+    options.flatten = read.flatten;
+    let image = match (read.threshold, read.dither) {
+        (Threshold::Fixed(threshold), None) => match read.weak_threshold {
+            Some(weak) => hysteresis(
+                &read_gray(path, data, &options)?,
+                threshold,
+                weak,
+                read.connectivity,
+            )?,
+            None => {
+                options.threshold = threshold;
+                read_image(path, data, &options)?
+            }
+        },
+        (Threshold::Fixed(threshold), Some(dither)) => {
+            read_gray(path, data, &options)?.dither(dither, threshold, read.serpentine)
+        }
+        (Threshold::Auto(method), dither) => {
+            let gray = read_gray(path, data, &options)?;
+            let threshold = gray.auto_threshold(method);
+            eprintln!("{path}: threshold {threshold}");
+            match (dither, read.weak_threshold) {
+                (Some(dither), _) => gray.dither(dither, threshold, read.serpentine),
+                (None, Some(weak)) => hysteresis(&gray, threshold, weak, read.connectivity)?,
+                (None, None) => gray.threshold(threshold),
+            }
+        }
+        (Threshold::Local(_), None) if read.weak_threshold.is_some() => {
+            return Err("hysteresis needs a fixed or automatic threshold".into())
+        }
+        (Threshold::Local(local), None) => read_gray(path, data, &options)?.local_threshold(
+            local,
+            read.window,
+            read.k.unwrap_or(local.default_k()),
+        ),
+        (Threshold::Local(_), Some(_)) => {
+            return Err("dithering needs a fixed or automatic threshold".into())
+        }
+    };
+    Ok(image)
+}
+
+// the black page followed by the pages of the colors that are kept separate
+fn process(
+    path: &str,
+    debug_path: Option<&str>,
+    args: &Args,
+) -> Result<Vec<Image>, Box<dyn Error>> {
+    let data = read_input(path)?;
+    let image_black = read_black(path, &data, &args.read)?;
+    let fill_ratio = args.fill_ratio.unwrap_or(f64::INFINITY);
+    let mut image_black = if args.obj_threshold == 0
+        && args.keep.is_none()
+        && args.fill_holes.is_none()
+        && debug_path.is_none()
+    {
+        image_black
+    } else {
+        // the debug image shows the same pass the output comes from
+        let mut filter = ObjectFilter::new(
+            &image_black,
+            args.obj_threshold,
+            true,
+            args.read.connectivity,
+            args.keep.as_ref(),
+        );
+        if let Some(max_size) = args.fill_holes {
+            filter = filter.fill_holes(max_size, fill_ratio);
+        }
+        if let Some(debug_path) = debug_path {
+            let mut writer = BufWriter::new(std::fs::File::create(debug_path)?);
+            filter.write_png(&mut writer)?;
+            writer.flush()?;
+        }
+        filter
+            .to_image()
+            .with_metadata(image_black.metadata().clone())
+    };
+
+    let mut extra_colors = args.extra_colors.clone();
     if args.color_threshold > 0.0 {
-        let image_yellow = Image::from_png_filter(
-            args.input_path,
-            args.red as f64,
-            args.green as f64,
-            args.blue as f64,
-            args.color_threshold,
+        extra_colors.insert(
+            0,
+            ExtraColor {
+                rgb: [args.red, args.green, args.blue],
+                threshold: args.color_threshold,
+                mode: ColorMode::Merge,
+            },
+        );
+    }
+    let mut separate = Vec::new();
+    for color in extra_colors {
+        let [red, green, blue] = color.rgb.map(|channel| channel as f64);
+        let mut options = DecodeOptions::new(red, green, blue, color.threshold);
+        options.background = args.read.background.map(|channel| channel as f64);
+        options.metric = args.metric;
+        options.hsv_range = args.hsv_range;
+        options.flatten = args.read.flatten;
+        let mut image_color = read_image(path, &data, &options)?.filter_objects(
             args.obj_threshold,
             false,
-        )
-        .unwrap();
-        for object in image_yellow.full_cutout().objects(false) {
-            for object in image_yellow
-                .full_cutout()
-                .cutout(
-                    object.width(),
-                    object.height(),
-                    object.xmin(),
-                    object.ymin(),
-                )
-                .objects(true)
-                .into_iter()
+            args.read.connectivity,
+            args.keep.as_ref(),
+        );
+        if let Some(max_size) = args.fill_holes {
+            image_color = image_color.fill_holes(max_size, fill_ratio, args.read.connectivity);
+        }
+        match color.mode {
+            ColorMode::Merge => image_black.merge(&image_color, args.read.connectivity),
+            ColorMode::Erase => {
+                image_black.clear_pixels(image_color.full_cutout().pixels(false, true))
+            }
+            ColorMode::KeepSeparate => {
+                image_black.clear_pixels(image_color.full_cutout().pixels(false, true));
+                separate.push(image_color);
+            }
+        }
+    }
+
+    let mut pages = vec![image_black];
+    pages.extend(separate);
+    for page in pages.iter_mut() {
+        if args.left_border > 0 {
+            page.clear_border_left(args.left_border);
+        }
+        if args.right_border > 0 {
+            page.clear_border_right(args.right_border);
+        }
+
+        if args.width > 0 {
+            *page = page.horizontal_padding(args.width)?
+        }
+        if let Some(dpi) = args.dpi {
+            page.metadata_mut().resolution = Some((dpi, dpi));
+        }
+    }
+    Ok(pages)
+}
+
+// clusters this close to the paper are shading of the paper
+const PAPER_DISTANCE: f64 = 0.1;
+// clusters with less saturation are grays
+const INK_SATURATION: f64 = 0.15;
+// grays darker than this share of the paper's luminance are ink, lighter ones shades of the paper
+const INK_LUMINANCE: f64 = 0.75;
+
+// whether each cluster is paper, ink or a color, the first cluster is the most common one
+fn roles(clusters: &[Cluster]) -> Vec<&'static str> {
+    let Some(paper) = clusters.first() else {
+        return Vec::new();
+    };
+    let ink = clusters
+        .iter()
+        .min_by(|a, b| a.luminance().total_cmp(&b.luminance()))
+        .filter(|ink| *ink != paper);
+    clusters
+        .iter()
+        .map(|cluster| {
+            let gray = hsv(cluster.color)[1] < INK_SATURATION;
+            if cluster.distance(paper) < PAPER_DISTANCE {
+                "paper"
+            } else if Some(cluster) == ink
+                || gray && cluster.luminance() < paper.luminance() * INK_LUMINANCE
             {
-                image_black.clear_pixels(object)
+                "ink"
+            } else if gray {
+                "paper"
+            } else {
+                "color"
             }
-            image_black.set_pixels(object)
+        })
+        .collect()
+}
+
+fn colors(path: &str, clusters: usize, background: [u8; 3]) -> Result<(), Box<dyn Error>> {
+    let data = read_input(path)?;
+    let background = background.map(|channel| channel as f64);
+    let histogram = if is_netpbm(path, &data) {
+        read_pnm(data.as_slice(), background, |_, _, pixels| {
+            ColorHistogram::new(pixels)
+        })?
+    } else {
+        read_png(data.as_slice(), background, |_, _, pixels| {
+            ColorHistogram::new(pixels)
+        })?
+        .0
+    };
+    let clusters = kmeans(&histogram.colors(), clusters);
+    if clusters.is_empty() {
+        return Err("the image has no pixels".into());
+    }
+    let total = clusters.iter().map(|cluster| cluster.count).sum::<usize>() as f64;
+
+    let mut writer = BufWriter::new(std::io::stdout().lock());
+    for (cluster, role) in clusters.iter().zip(roles(&clusters)) {
+        let [r, g, b] = cluster.rgb();
+        let share = cluster.count as f64 / total * 100.0;
+        write!(writer, "{role:<6} {r:>3},{g:>3},{b:>3} {share:>6.2}%")?;
+        if role == "color" {
+            // half the way to the nearest other cluster, so the threshold takes none of them
+            let threshold = clusters
+                .iter()
+                .filter(|other| *other != cluster)
+                .map(|other| cluster.distance(other) / 2.0)
+                .fold(1.0, f64::min);
+            let threshold = (threshold * 100.0).floor() / 100.0;
+            write!(writer, "  --red {r} --green {g} --blue {b} --color-threshold {threshold} (--color {r},{g},{b}:{threshold})")?;
         }
+        writeln!(writer)?;
     }
+    writer.flush()?;
+    Ok(())
+}
+
+// one row per object, in the order of its first pixel
+fn objects(path: &str, read: &ReadArgs, format: Table) -> Result<(), Box<dyn Error>> {
+    let data = read_input(path)?;
+    let image = read_black(path, &data, read)?;
+    let connectivity = read.connectivity;
+    let labels = Labels::new(&image, true, connectivity);
+    let fields = [
+        "id",
+        "xmin",
+        "xmax",
+        "ymin",
+        "ymax",
+        "size",
+        "centroid_x",
+        "centroid_y",
+        "perimeter",
+        "holes",
+        "euler_number",
+        "density",
+        "touches_border",
+    ];
 
-    if args.left_border > 0 {
-        image_black.clear_border_left(args.left_border);
+    let mut writer = BufWriter::new(std::io::stdout().lock());
+    match format {
+        Table::Json => writeln!(writer, "[")?,
+        Table::Csv => writeln!(writer, "{}", fields.join(","))?,
     }
-    if args.right_border > 0 {
-        image_black.clear_border_right(args.right_border);
+    let count = labels.components().len();
+    for idx in 0..count {
+        let object = labels.object(idx);
+        let (x, y) = object.centroid();
+        let values = [
+            idx.to_string(),
+            object.xmin().to_string(),
+            object.xmax().to_string(),
+            object.ymin().to_string(),
+            object.ymax().to_string(),
+            object.size().to_string(),
+            format!("{x:.2}"),
+            format!("{y:.2}"),
+            object.edge_count().to_string(),
+            object.holes(connectivity).to_string(),
+            object.euler_number(connectivity).to_string(),
+            format!("{:.4}", object.density()),
+            object
+                .touches_border(image.width(), image.height(), 0, 0)
+                .to_string(),
+        ];
+        match format {
+            Table::Json => {
+                let pairs = fields
+                    .iter()
+                    .zip(&values)
+                    .map(|(field, value)| format!("\"{field}\": {value}"))
+                    .collect::<Vec<String>>();
+                let separator = if idx + 1 < count { "," } else { "" };
+                writeln!(writer, "  {{{}}}{separator}", pairs.join(", "))?;
+            }
+            Table::Csv => writeln!(writer, "{}", values.join(","))?,
+        }
+    }
+    if format == Table::Json {
+        writeln!(writer, "]")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    match &args.command {
+        Some(Command::Colors {
+            input_path,
+            clusters,
+            background,
+        }) => return colors(input_path, *clusters, *background),
+        Some(Command::Objects {
+            input_path,
+            read,
+            format,
+        }) => return objects(input_path, read, *format),
+        None => {}
+    }
+
+    let (output_path, input_paths) = args.split_paths()?;
+    let images = input_paths
+        .iter()
+        .enumerate()
+        .map(|(page, path)| {
+            let debug_path = args.debug_labels.as_deref().map(|debug_path| {
+                if input_paths.len() > 1 {
+                    page_path(debug_path, page + 1)
+                } else {
+                    debug_path.to_string()
+                }
+            });
+            process(path, debug_path.as_deref(), args)
+        })
+        .collect::<Result<Vec<Vec<Image>>, Box<dyn Error>>>()?
+        .concat();
+    write_images(&images, output_path, args)
+}
+
+fn main() {
+    if let Err(err) = run(&Args::parse()) {
+        // a closed pipe like in bim objects page.png | head is no failure
+        let io_error = match err.downcast_ref::<png::EncodingError>() {
+            Some(png::EncodingError::IoError(err)) => Some(err),
+            _ => err.downcast_ref::<std::io::Error>(),
+        };
+        let broken_pipe = io_error.is_some_and(|err| err.kind() == std::io::ErrorKind::BrokenPipe);
+        if !broken_pipe {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn options_between_paths() {
+        for line in [
+            "bim -t 0.6 a.png b.png out.pdf",
+            "bim a.png -t 0.6 out.png",
+            "bim a.png b.png -t 0.6 out.pdf",
+        ] {
+            let args = Args::try_parse_from(line.split(' ')).unwrap();
+            let (output_path, input_paths) = args.split_paths().unwrap();
+            assert_eq!(args.read.threshold, Threshold::Fixed(0.6));
+            assert_eq!(input_paths[0], "a.png");
+            assert!(output_path.starts_with("out."));
+        }
+        let args = Args::try_parse_from(["bim", "a.png"]).unwrap();
+        assert!(args.split_paths().is_err());
+    }
+
+    #[test]
+    fn stdin_and_stdout_round_trip() {
+        let mut image = Image::new_empty(19, 7);
+        for x in 0..19 {
+            image.set(x, x % 7, true);
+            image.set(x, 3, x % 3 == 0);
+        }
+        let args = Args::try_parse_from(["bim", "-", "-"]).unwrap();
+        for format in [Format::Png, Format::Pbm] {
+            let mut output = Cursor::new(Vec::new());
+            write_output(&[image.clone()], format, &mut output, &args).unwrap();
+
+            // without an extension the format is told by the data itself
+            let mut input = Cursor::new(output.into_inner());
+            let mut data = Vec::new();
+            input.read_to_end(&mut data).unwrap();
+            assert_eq!(data.starts_with(b"P4"), format == Format::Pbm);
+            let decoded = read_black("-", &data, &args.read).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (19, 7));
+            for y in 0..7 {
+                assert!(decoded.line(y).eq(image.line(y)));
+            }
+        }
+    }
+
+    #[test]
+    fn weak_threshold_below_threshold() {
+        let gray = GrayImage::new(4, 1, vec![0.1, 0.5, 0.9, 0.4]);
+        let image = hysteresis(&gray, 0.3, 0.6, Connectivity::Four).unwrap();
+        assert!(image.line(0).eq([true, true, false, false]));
+        assert!(hysteresis(&gray, 0.3, 0.3, Connectivity::Four).is_ok());
+        assert!(hysteresis(&gray, 0.6, 0.3, Connectivity::Four).is_err());
+    }
+
+    #[test]
+    fn roles_of_clusters() {
+        let cluster = |color: [f64; 3]| Cluster { color, count: 1 };
+        let clusters = [
+            cluster([250.0, 250.0, 245.0]),
+            cluster([20.0, 20.0, 25.0]),
+            cluster([213.0, 213.0, 206.0]),
+            cluster([120.0, 120.0, 118.0]),
+            cluster([254.0, 218.0, 13.0]),
+        ];
+        assert_eq!(roles(&clusters), ["paper", "ink", "paper", "ink", "color"]);
+        assert!(roles(&[]).is_empty());
     }
 
-    if args.width > 0 {
-        image_black = image_black.horizontal_padding(args.width).unwrap()
+    #[test]
+    fn debug_paths_per_page() {
+        assert_eq!(page_path("debug.png", 2), "debug-2.png");
+        assert_eq!(page_path("out/labels", 1), "out/labels-1");
     }
-    image_black.to_png(args.output_path).unwrap();
 }
@@ -0,0 +1,160 @@
+use flate2::write::ZlibEncoder;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+pub const DEFAULT_DPI: f64 = 300.0;
+
+const INCHES_PER_METER: f64 = 1.0 / 0.0254;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub resolution: Option<(f64, f64)>,
+    pub gamma: Option<u32>,
+    pub icc_profile: Option<Vec<u8>>,
+    pub text: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    pub fn from_png_info(info: &png::Info) -> Self {
+        let resolution = info
+            .pixel_dims
+            .filter(|dims| dims.unit == png::Unit::Meter)
+            .map(|dims| (to_dpi(dims.xppu), to_dpi(dims.yppu)));
+        let gamma = info.source_gamma.map(|gamma| gamma.into_scaled());
+        let icc_profile = info.icc_profile.as_ref().map(|profile| profile.to_vec());
+
+        let mut text = BTreeMap::new();
+        for chunk in &info.uncompressed_latin1_text {
+            text.insert(chunk.keyword.clone(), chunk.text.clone());
+        }
+        for chunk in &info.compressed_latin1_text {
+            if let Ok(value) = chunk.get_text() {
+                text.insert(chunk.keyword.clone(), value);
+            }
+        }
+        for chunk in &info.utf8_text {
+            if let Ok(value) = chunk.get_text() {
+                text.insert(chunk.keyword.clone(), value);
+            }
+        }
+
+        Self {
+            resolution,
+            gamma,
+            icc_profile,
+            text,
+        }
+    }
+
+    pub fn dpi(&self) -> (f64, f64) {
+        self.resolution.unwrap_or((DEFAULT_DPI, DEFAULT_DPI))
+    }
+
+    pub fn set_png_info<W: Write>(
+        &self,
+        encoder: &mut png::Encoder<W>,
+    ) -> Result<(), png::EncodingError> {
+        encoder.set_pixel_dims(self.resolution.map(|(x, y)| png::PixelDimensions {
+            xppu: (x * INCHES_PER_METER).round() as u32,
+            yppu: (y * INCHES_PER_METER).round() as u32,
+            unit: png::Unit::Meter,
+        }));
+        if let Some(gamma) = self.gamma {
+            encoder.set_source_gamma(png::ScaledFloat::from_scaled(gamma));
+        }
+        for (keyword, text) in &self.text {
+            if text.is_ascii() {
+                encoder.add_text_chunk(keyword.clone(), text.clone())?;
+            } else {
+                encoder.add_itxt_chunk(keyword.clone(), text.clone())?;
+            }
+        }
+        Ok(())
+    }
+
+    // the png encoder has no iCCP support, so the chunk is written by hand after the header,
+    // a profile that isn't gray, like the rgb profile of a color scan, is left out on purpose
+    pub fn write_png_chunks<W: Write>(
+        &self,
+        writer: &mut png::Writer<W>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(profile) = self.icc_profile.as_ref().filter(|profile| is_gray(profile)) {
+            let mut data = b"ICC profile\0\0".to_vec();
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(profile)?;
+            data.extend(encoder.finish()?);
+            writer.write_chunk(png::chunk::iCCP, &data)?;
+        }
+        Ok(())
+    }
+}
+
+// pixels per meter are whole numbers, so 300 dpi comes back as 299.9994
+fn to_dpi(ppm: u32) -> f64 {
+    (ppm as f64 / INCHES_PER_METER * 10.0).round() / 10.0
+}
+
+// output is grayscale, where an rgb or cmyk profile of a color scan would be invalid
+fn is_gray(profile: &[u8]) -> bool {
+    profile.get(16..20) == Some(b"GRAY")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+
+    #[test]
+    fn png_roundtrip() {
+        let mut metadata = Metadata {
+            resolution: Some((300.0, 150.0)),
+            gamma: Some(45455),
+            ..Default::default()
+        };
+        metadata
+            .text
+            .insert("Software".to_string(), "scanner".to_string());
+
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, 1, 1);
+        metadata.set_png_info(&mut encoder).unwrap();
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&[0])
+            .unwrap();
+
+        let reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+        let decoded = Metadata::from_png_info(reader.info());
+        assert_eq!(decoded.resolution, metadata.resolution);
+        assert_eq!(decoded.gamma, metadata.gamma);
+        assert_eq!(decoded.text, metadata.text);
+    }
+
+    #[test]
+    fn icc_profile_only_when_gray() {
+        // the header of a profile, whose color space is at byte 16
+        let profile = |color_space: &[u8; 4]| {
+            let mut profile = vec![0; 128];
+            profile[16..20].copy_from_slice(color_space);
+            profile
+        };
+        for (color_space, kept) in [(b"GRAY", true), (b"RGB ", false)] {
+            let metadata = Metadata {
+                icc_profile: Some(profile(color_space)),
+                ..Default::default()
+            };
+            let mut data = Vec::new();
+            Image::new_empty(1, 1)
+                .with_metadata(metadata.clone())
+                .write_png(&mut data)
+                .unwrap();
+            let reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+            let decoded = Metadata::from_png_info(reader.info());
+            assert_eq!(decoded.icc_profile.is_some(), kept);
+            if kept {
+                assert_eq!(decoded.icc_profile, metadata.icc_profile);
+            }
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::encoder::OneBitEncoder;
+use std::error::Error;
+
+#[derive(Debug, Clone)]
+pub struct Netpbm {
+    width: usize,
+    height: usize,
+    channels: usize,
+    max_value: u16,
+    samples: Vec<u16>,
+}
+
+impl Netpbm {
+    pub fn decode(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut header = Header::new(data);
+        let magic = header.token()?;
+        match magic.as_str() {
+            "P1" | "P4" => {
+                let width = header.number()?;
+                let height = header.number()?;
+                let bits = if magic == "P1" {
+                    header.ascii_bits(width * height)?
+                } else {
+                    header.binary_bits(width, height)?
+                };
+                // a set bit is black in pbm, so it is stored as gray level 0
+                let samples = bits.into_iter().map(|bit| 1 - bit).collect();
+                Ok(Self::new(width, height, 1, 1, samples))
+            }
+            "P2" | "P3" | "P5" | "P6" => {
+                let width = header.number()?;
+                let height = header.number()?;
+                let max_value = header.max_value()?;
+                let channels = if magic == "P2" || magic == "P5" { 1 } else { 3 };
+                let count = width * height * channels;
+                let samples = if magic == "P2" || magic == "P3" {
+                    header.ascii_samples(count)?
+                } else {
+                    header.binary_samples(count, max_value)?
+                };
+                Ok(Self::new(width, height, channels, max_value, samples))
+            }
+            "P7" => {
+                let mut width = None;
+                let mut height = None;
+                let mut depth = None;
+                let mut max_value = None;
+                loop {
+                    match header.token()?.as_str() {
+                        "WIDTH" => width = Some(header.number()?),
+                        "HEIGHT" => height = Some(header.number()?),
+                        "DEPTH" => depth = Some(header.number()?),
+                        "MAXVAL" => max_value = Some(header.max_value()?),
+                        "TUPLTYPE" => {
+                            header.token()?;
+                        }
+                        "ENDHDR" => break,
+                        token => return Err(format!("unknown pam header field {token}").into()),
+                    }
+                }
+                let width = width.ok_or("pam header without WIDTH")?;
+                let height = height.ok_or("pam header without HEIGHT")?;
+                let channels = depth.ok_or("pam header without DEPTH")?;
+                let max_value = max_value.ok_or("pam header without MAXVAL")?;
+                if !(1..=4).contains(&channels) {
+                    return Err(format!("unsupported pam depth {channels}").into());
+                }
+                let samples = header.binary_samples(width * height * channels, max_value)?;
+                Ok(Self::new(width, height, channels, max_value, samples))
+            }
+            _ => Err(format!("unsupported netpbm variant ({magic})").into()),
+        }
+    }
+
+    pub fn new(
+        width: usize,
+        height: usize,
+        channels: usize,
+        max_value: u16,
+        samples: Vec<u16>,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            channels,
+            max_value,
+            samples,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn max_value(&self) -> u16 {
+        self.max_value
+    }
+
+    pub fn samples(&self) -> &[u16] {
+        &self.samples
+    }
+}
+
+pub fn encode_pbm<'a, T>(width: usize, height: usize, rows: T, plain: bool) -> Vec<u8>
+where
+    T: IntoIterator<Item = &'a [bool]>,
+{
+    let mut result = if plain {
+        format!("P1\n{width} {height}\n").into_bytes()
+    } else {
+        format!("P4\n{width} {height}\n").into_bytes()
+    };
+    for row in rows {
+        if plain {
+            for line in row.chunks(35) {
+                for pixel in line {
+                    result.push(if *pixel { b'1' } else { b'0' });
+                    result.push(b' ');
+                }
+                result.pop();
+                result.push(b'\n');
+            }
+        } else {
+            let padding = (8 - width % 8) % 8;
+            let bytes = OneBitEncoder::new(row)
+                .map(|byte| !byte)
+                .collect::<Vec<u8>>();
+            if let Some((last, bytes)) = bytes.split_last() {
+                result.extend(bytes);
+                result.push(last & (0xff << padding));
+            }
+        }
+    }
+    result
+}
+
+struct Header<'a> {
+    data: &'a [u8],
+    idx: usize,
+}
+
+impl<'a> Header<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let idx = 0;
+        Self { data, idx }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.idx < self.data.len() {
+            if self.data[self.idx] == b'#' {
+                while self.idx < self.data.len() && self.data[self.idx] != b'\n' {
+                    self.idx += 1;
+                }
+            } else if !self.data[self.idx].is_ascii_whitespace() {
+                return;
+            }
+            self.idx += 1;
+        }
+    }
+
+    fn token(&mut self) -> Result<String, Box<dyn Error>> {
+        self.skip_whitespace();
+        let start = self.idx;
+        while self.idx < self.data.len() && !self.data[self.idx].is_ascii_whitespace() {
+            self.idx += 1;
+        }
+        if start == self.idx {
+            return Err("unexpected end of netpbm data".into());
+        }
+        Ok(String::from_utf8_lossy(&self.data[start..self.idx]).into_owned())
+    }
+
+    fn number(&mut self) -> Result<usize, Box<dyn Error>> {
+        Ok(self.token()?.parse()?)
+    }
+
+    fn max_value(&mut self) -> Result<u16, Box<dyn Error>> {
+        let max_value = self.token()?.parse()?;
+        if max_value == 0 {
+            return Err("netpbm maxval has to be at least 1".into());
+        }
+        Ok(max_value)
+    }
+
+    fn raster(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        // exactly one whitespace character separates the header from the raster
+        let start = self.idx + 1;
+        if start + len > self.data.len() {
+            return Err("netpbm raster is shorter than announced in the header".into());
+        }
+        self.idx = start + len;
+        Ok(&self.data[start..start + len])
+    }
+
+    fn ascii_bits(&mut self, count: usize) -> Result<Vec<u16>, Box<dyn Error>> {
+        let mut bits = Vec::with_capacity(count);
+        while bits.len() < count {
+            self.skip_whitespace();
+            match self.data.get(self.idx) {
+                Some(b'0') => bits.push(0),
+                Some(b'1') => bits.push(1),
+                _ => return Err("invalid or missing pixel in plain pbm".into()),
+            }
+            self.idx += 1;
+        }
+        Ok(bits)
+    }
+
+    fn binary_bits(&mut self, width: usize, height: usize) -> Result<Vec<u16>, Box<dyn Error>> {
+        let row_len = width.div_ceil(8);
+        let raster = self.raster(row_len * height)?;
+        Ok(raster
+            .chunks(row_len)
+            .flat_map(|row| (0..width).map(move |x| ((row[x / 8] >> (7 - x % 8)) & 1) as u16))
+            .collect())
+    }
+
+    fn ascii_samples(&mut self, count: usize) -> Result<Vec<u16>, Box<dyn Error>> {
+        (0..count)
+            .map(|_| Ok(self.token()?.parse::<u16>()?))
+            .collect()
+    }
+
+    fn binary_samples(&mut self, count: usize, max_value: u16) -> Result<Vec<u16>, Box<dyn Error>> {
+        if max_value < 256 {
+            Ok(self
+                .raster(count)?
+                .iter()
+                .map(|byte| *byte as u16)
+                .collect())
+        } else {
+            Ok(self
+                .raster(count * 2)?
+                .chunks(2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_plain_pbm() {
+        let pbm = Netpbm::decode(b"P1\n# comment\n3 2\n1 0 1\n010").unwrap();
+        assert_eq!((pbm.width(), pbm.height(), pbm.max_value()), (3, 2, 1));
+        assert_eq!(pbm.samples(), &[0, 1, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn decode_binary_pgm() {
+        let pgm = Netpbm::decode(b"P5 2 1 65535\n\x00\x01\xff\xff").unwrap();
+        assert_eq!(pgm.max_value(), 65535);
+        assert_eq!(pgm.samples(), &[1, 65535]);
+    }
+
+    #[test]
+    fn encode_decode_pbm() {
+        let rows: Vec<&[bool]> = vec![
+            &[
+                true, false, false, false, false, false, false, false, false, true,
+            ],
+            &[false; 10],
+        ];
+        let pbm = Netpbm::decode(&encode_pbm(10, 2, rows.clone(), false)).unwrap();
+        let plain = Netpbm::decode(&encode_pbm(10, 2, rows, true)).unwrap();
+        assert_eq!(pbm.samples(), plain.samples());
+        assert_eq!(&pbm.samples()[..10], &[0, 1, 1, 1, 1, 1, 1, 1, 1, 0]);
+    }
+}
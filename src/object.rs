@@ -1,4 +1,6 @@
+use crate::contour::{boundaries, Boundary};
 use crate::image::Image;
+use crate::label::{Connectivity, Labels};
 use std::collections::HashSet;
 
 pub trait Object: IntoIterator<Item = Pixel> + Clone {
@@ -35,7 +37,64 @@ pub trait Object: IntoIterator<Item = Pixel> + Clone {
         image.set_pixels(self.into_iter().map(|pixel| pixel.subx(xmin).suby(ymin)));
         image
     }
-    fn connected_grow(&self, image: &Image, max_size: Option<usize>) -> Option<SimpleObject> {
+    fn centroid(&self) -> (f64, f64) {
+        let (x, y) = self
+            .clone()
+            .into_iter()
+            .fold((0, 0), |(x, y), pixel| (x + pixel.x, y + pixel.y));
+        (x as f64 / self.size() as f64, y as f64 / self.size() as f64)
+    }
+    // the number of pixel edges between the object and the background, the crack length around
+    // the pixels, longer than the perimeter of its boundaries through the pixel centers
+    fn edge_count(&self) -> usize {
+        let image = self.clone().to_image();
+        (0..image.height())
+            .flat_map(|y| (0..image.width()).map(move |x| (x, y)))
+            .filter(|(x, y)| image.get(*x, *y))
+            .map(|(x, y)| {
+                [
+                    (x.wrapping_sub(1), y),
+                    (x + 1, y),
+                    (x, y.wrapping_sub(1)),
+                    (x, y + 1),
+                ]
+                .into_iter()
+                .filter(|(x, y)| !image.get(*x, *y))
+                .count()
+            })
+            .sum()
+    }
+    // the background regions enclosed by the object, connected the opposite way of the object
+    fn holes(&self, connectivity: Connectivity) -> usize {
+        let image = self.clone().to_image();
+        Labels::new(&image, false, connectivity.opposite())
+            .components()
+            .iter()
+            .filter(|hole| !hole.touches_border(image.width(), image.height()))
+            .count()
+    }
+    fn euler_number(&self, connectivity: Connectivity) -> isize {
+        1 - self.holes(connectivity) as isize
+    }
+    // the share of the bounding box covered by the object
+    fn density(&self) -> f64 {
+        self.size() as f64 / (self.width() * self.height()) as f64
+    }
+    // the outer boundary and the boundaries of the holes in image coordinates, whose perimeters
+    // walk the pixel centers unlike the edge count
+    fn boundaries(&self, connectivity: Connectivity) -> Vec<Boundary> {
+        let (offx, offy) = (self.xmin(), self.ymin());
+        boundaries(&self.clone().to_image(), connectivity)
+            .into_iter()
+            .map(|boundary| boundary.offset(offx, offy))
+            .collect()
+    }
+    fn connected_grow(
+        &self,
+        image: &Image,
+        max_size: Option<usize>,
+        connectivity: Connectivity,
+    ) -> Option<SimpleObject> {
         let mut queue = self.clone().into_iter().collect::<Vec<Pixel>>();
         let mut result = SimpleObject::new();
 
@@ -51,17 +110,13 @@ pub trait Object: IntoIterator<Item = Pixel> + Clone {
             if !result.add_pixel(pixel.clone()) {
                 continue;
             }
-            if pixel.x < image.width() {
-                queue.push(pixel.addx(1));
-            }
-            if pixel.y < image.height() {
-                queue.push(pixel.addy(1));
-            }
-            if pixel.x > 0 {
-                queue.push(pixel.subx(1));
-            }
-            if pixel.y > 0 {
-                queue.push(pixel.suby(1));
+            for (dx, dy) in connectivity.offsets() {
+                if let (Some(x), Some(y)) = (
+                    pixel.x.checked_add_signed(*dx),
+                    pixel.y.checked_add_signed(*dy),
+                ) {
+                    queue.push(Pixel::new(x, y));
+                }
             }
         }
 
@@ -160,12 +215,18 @@ impl Object for Pixel {
     }
 }
 
+impl Default for SimpleObject {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SimpleObject {
     pub fn new() -> Self {
         let pixel = HashSet::new();
-        let xmin = std::usize::MAX;
+        let xmin = usize::MAX;
         let xmax = 0;
-        let ymin = std::usize::MAX;
+        let ymin = usize::MAX;
         let ymax = 0;
         let size = 0;
         Self {
@@ -191,8 +252,11 @@ impl SimpleObject {
         if pixel.y < self.ymin {
             self.ymin = pixel.y;
         }
-        self.size = self.size + 1;
-        self.pixel.insert(pixel)
+        let inserted = self.pixel.insert(pixel);
+        if inserted {
+            self.size += 1;
+        }
+        inserted
     }
 
     pub fn xmin(&self) -> usize {
@@ -234,9 +298,9 @@ impl FromIterator<Pixel> for SimpleObject {
     where
         T: IntoIterator<Item = Pixel>,
     {
-        let mut iter = iter.into_iter();
+        let iter = iter.into_iter();
         let mut result = Self::new();
-        while let Some(pixel) = iter.next() {
+        for pixel in iter {
             result.add_pixel(pixel);
         }
         result
@@ -277,12 +341,12 @@ pub struct Rectangle {
 
 impl Rectangle {
     pub fn new(xmin: usize, xmax: usize, ymin: usize, ymax: usize) -> Self {
-        return Self {
+        Self {
             xmin,
             xmax,
             ymin,
             ymax,
-        };
+        }
     }
 }
 
@@ -319,10 +383,10 @@ impl Iterator for RectanglePixelIterator {
             return None;
         }
         let pixel = Pixel::new(self.x, self.y);
-        self.x = self.x + 1;
+        self.x += 1;
         if self.x > self.xmax {
             self.x = self.xmin;
-            self.y = self.y + 1;
+            self.y += 1;
         }
         if self.y > self.ymax {
             self.finished = true;
@@ -376,7 +440,7 @@ where
             return None;
         }
         let mut lastx = self.xmin;
-        while let Some(pixel) = self.iter.next() {
+        for pixel in self.iter.by_ref() {
             if pixel.x > lastx + 1 || pixel.x < lastx {
                 let rectangle = Rectangle::new(self.xmin, lastx, self.ymin, self.ymin);
                 self.xmin = pixel.x;
@@ -474,13 +538,18 @@ impl RectangleCollection {
         self.rectangles.push(rectangle);
     }
 
-    pub fn bottom_touch(&self, rectangle: &Rectangle) -> bool {
+    pub fn bottom_touch(&self, rectangle: &Rectangle, connectivity: Connectivity) -> bool {
+        // with 8-connectivity the rows also touch when they are one pixel apart
+        let reach = match connectivity {
+            Connectivity::Four => 0,
+            Connectivity::Eight => 1,
+        };
         (self.ymax == rectangle.ymin
-            && rectangle.xmin <= self.last_bottom_xmax
-            && self.last_bottom_xmin <= rectangle.xmax)
+            && rectangle.xmin <= self.last_bottom_xmax + reach
+            && self.last_bottom_xmin <= rectangle.xmax + reach)
             || (self.ymax + 1 == rectangle.ymin
-                && rectangle.xmin <= self.bottom_xmax
-                && self.bottom_xmin <= rectangle.xmax)
+                && rectangle.xmin <= self.bottom_xmax + reach
+                && self.bottom_xmin <= rectangle.xmax + reach)
     }
 
     pub fn rectangles(self) -> Vec<Rectangle> {
@@ -546,9 +615,36 @@ impl Object for RectangleCollection {
 }
 
 #[cfg(test)]
+#[allow(non_snake_case)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn measures_of_a_ring() {
+        let ring = Rectangle::new(3, 5, 7, 9)
+            .into_iter()
+            .filter(|pixel| *pixel != Pixel::new(4, 8))
+            .collect::<SimpleObject>();
+        assert_eq!(ring.centroid(), (4.0, 8.0));
+        assert_eq!(ring.edge_count(), 16);
+        assert_eq!(ring.holes(Connectivity::Four), 1);
+        assert_eq!(ring.euler_number(Connectivity::Four), 0);
+        assert_eq!(ring.density(), 8.0 / 9.0);
+    }
+
+    #[test]
+    fn grow_counts_pixels_once() {
+        let image = Image::from_rows(&["###", "#.#", "###"]);
+        let grown = Rectangle::new(0, 2, 0, 0)
+            .connected_grow(&image, None, Connectivity::Four)
+            .unwrap();
+        assert_eq!(grown.size(), 8);
+        let mut object = SimpleObject::new();
+        assert!(object.add_pixel(Pixel::new(1, 1)));
+        assert!(!object.add_pixel(Pixel::new(1, 1)));
+        assert_eq!(object.size(), 1);
+    }
+
     #[test]
     fn rectanglePixelIterator() {
         let rectangle = Rectangle::new(0, 0, 0, 0);
@@ -0,0 +1,260 @@
+use crate::image::Image;
+use crate::tiff::Compression;
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Paper {
+    /// 210mm x 297mm
+    A4,
+    /// 8.5in x 11in
+    Letter,
+}
+
+impl Paper {
+    pub fn size(&self) -> (f64, f64) {
+        match self {
+            Self::A4 => (595.276, 841.89),
+            Self::Letter => (612.0, 792.0),
+        }
+    }
+}
+
+pub fn encode_pdf(images: &[&Image], compression: Compression, paper: Option<Paper>) -> Vec<u8> {
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    let pages_id = 2;
+    let mut kids = Vec::new();
+
+    for image in images {
+        let image_id = 3 + objects.len();
+        let content_id = image_id + 1;
+        let page_id = image_id + 2;
+
+        let mut data = compression.encode(image);
+        let (filter, parameters) = match compression {
+            Compression::Group4 | Compression::Group3 => (
+                "/CCITTFaxDecode",
+                format!(
+                    " /DecodeParms << /K {} /Columns {} /Rows {} /EndOfLine {} >>",
+                    if compression == Compression::Group4 {
+                        -1
+                    } else {
+                        0
+                    },
+                    image.width(),
+                    image.height(),
+                    compression == Compression::Group3,
+                ),
+            ),
+            Compression::PackBits => {
+                data.push(128);
+                ("/RunLengthDecode", " /Decode [1 0]".to_string())
+            }
+            Compression::Deflate => ("/FlateDecode", " /Decode [1 0]".to_string()),
+        };
+        let mut object = format!(
+            "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray /BitsPerComponent 1 /Filter {}{} /Length {} >>\nstream\n",
+            image.width(),
+            image.height(),
+            filter,
+            parameters,
+            data.len(),
+        )
+        .into_bytes();
+        object.extend(data);
+        object.extend(b"\nendstream");
+        objects.push(object);
+
+        let (xdpi, ydpi) = image.dpi();
+        let width = image.width() as f64 / xdpi * 72.0;
+        let height = image.height() as f64 / ydpi * 72.0;
+        let (page_width, page_height) = paper.map(|paper| paper.size()).unwrap_or((width, height));
+        let scale = (page_width / width).min(page_height / height).min(1.0);
+        let content = format!(
+            "q {:.3} 0 0 {:.3} {:.3} {:.3} cm /Im0 Do Q",
+            width * scale,
+            height * scale,
+            (page_width - width * scale) / 2.0,
+            (page_height - height * scale) / 2.0,
+        );
+        objects.push(
+            format!(
+                "<< /Length {} >>\nstream\n{}\nendstream",
+                content.len(),
+                content
+            )
+            .into_bytes(),
+        );
+
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {:.3} {:.3}] /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                pages_id, page_width, page_height, image_id, content_id,
+            )
+            .into_bytes(),
+        );
+        kids.push(format!("{page_id} 0 R"));
+    }
+
+    objects.insert(
+        0,
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            kids.len()
+        )
+        .into_bytes(),
+    );
+    objects.insert(
+        0,
+        format!("<< /Type /Catalog /Pages {pages_id} 0 R >>").into_bytes(),
+    );
+
+    let mut result = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (idx, object) in objects.iter().enumerate() {
+        offsets.push(result.len());
+        result.extend(format!("{} 0 obj\n", idx + 1).into_bytes());
+        result.extend(object);
+        result.extend(b"\nendobj\n");
+    }
+
+    let xref = result.len();
+    result.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+    for offset in offsets {
+        result.extend(format!("{offset:010} 00000 n \n").into_bytes());
+    }
+    result.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .into_bytes(),
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(data: &[u8], pattern: &str) -> Vec<usize> {
+        data.windows(pattern.len())
+            .enumerate()
+            .filter(|(_, window)| *window == pattern.as_bytes())
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    // the lines between the marker and the next occurrence of end, as text
+    fn section(data: &[u8], start: usize, end: &str) -> String {
+        let len = find(&data[start..], end)[0];
+        String::from_utf8_lossy(&data[start..start + len]).into_owned()
+    }
+
+    #[test]
+    fn xref_offsets_point_at_objects() {
+        let (first, second) = (Image::crossed(20, 10), Image::crossed(8, 30));
+        let pdf = encode_pdf(&[&first, &second], Compression::Group4, None);
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+
+        let startxref = find(&pdf, "startxref\n")[0];
+        let xref: usize = section(&pdf, startxref + 10, "\n").parse().unwrap();
+        assert_eq!(find(&pdf, "xref\n0 ")[0], xref);
+
+        let table = section(&pdf, xref, "trailer");
+        let mut lines = table.lines().skip(1);
+        assert_eq!(lines.next(), Some("0 9"));
+        assert_eq!(lines.next(), Some("0000000000 65535 f "));
+        let offsets: Vec<usize> = lines
+            .map(|line| {
+                assert_eq!(line.len(), 19);
+                assert!(line.ends_with(" 00000 n "));
+                line[..10].parse().unwrap()
+            })
+            .collect();
+        assert_eq!(offsets.len(), 8);
+        for (idx, offset) in offsets.into_iter().enumerate() {
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", idx + 1).as_bytes()));
+        }
+        assert!(section(&pdf, startxref - 40, "startxref").contains("<< /Size 9 /Root 1 0 R >>"));
+    }
+
+    #[test]
+    fn pages_of_multipage_input() {
+        let (first, second) = (Image::crossed(20, 10), Image::crossed(8, 30));
+        let pdf = encode_pdf(&[&first, &second], Compression::Deflate, None);
+        assert_eq!(
+            section(&pdf, find(&pdf, "2 0 obj\n")[0] + 8, "\n"),
+            "<< /Type /Pages /Kids [5 0 R 8 0 R] /Count 2 >>"
+        );
+        assert_eq!(find(&pdf, "/Type /Page ").len(), 2);
+        assert_eq!(find(&pdf, "/Parent 2 0 R").len(), 2);
+        assert_eq!(find(&pdf, "/Subtype /Image").len(), 2);
+
+        // 20 pixels at 300 dpi are 4.8 points
+        let page = section(&pdf, find(&pdf, "5 0 obj\n")[0], "endobj");
+        assert!(page.contains("/MediaBox [0 0 4.800 2.400]"));
+        assert!(page.contains("/XObject << /Im0 3 0 R >> >> /Contents 4 0 R"));
+    }
+
+    #[test]
+    fn image_filters() {
+        let image = Image::crossed(20, 10);
+        let dictionary = |compression| {
+            let pdf = encode_pdf(&[&image], compression, None);
+            let start = find(&pdf, "3 0 obj\n")[0] + 8;
+            let dictionary = section(&pdf, start, "\nstream\n");
+
+            // the stream holds exactly /Length bytes
+            let length: usize = dictionary
+                .split("/Length ")
+                .nth(1)
+                .and_then(|rest| rest.split(' ').next())
+                .unwrap()
+                .parse()
+                .unwrap();
+            let stream = start + dictionary.len() + 8;
+            assert!(pdf[stream + length..].starts_with(b"\nendstream"));
+            (dictionary, pdf[stream..stream + length].to_vec())
+        };
+
+        // the grays of the samples, 0 is black without a /Decode array and 1 with [1 0]
+        let pixels = |dictionary: &str, compression: Compression, data: &[u8]| {
+            let black_is_one = dictionary.contains("/Decode [1 0]");
+            assert!(black_is_one || !dictionary.contains("/Decode "));
+            let packed = compression.unpack(data).unwrap();
+            let decoded = Image::from_packed(20, 10, &packed, black_is_one);
+            assert_eq!(packed.len(), 3 * 10);
+            assert!((0..10).all(|y| decoded.line(y).eq(image.line(y))));
+        };
+
+        // without /BlackIs1 the black runs decode to 0, which is black in /DeviceGray
+        let (group4, data) = dictionary(Compression::Group4);
+        assert!(group4.contains(
+            "/Filter /CCITTFaxDecode /DecodeParms << /K -1 /Columns 20 /Rows 10 /EndOfLine false >>"
+        ));
+        assert!(!group4.contains("/Decode ") && !group4.contains("/BlackIs1"));
+        assert_eq!(data, Compression::Group4.encode(&image));
+
+        let (group3, data) = dictionary(Compression::Group3);
+        assert!(group3.contains(
+            "/Filter /CCITTFaxDecode /DecodeParms << /K 0 /Columns 20 /Rows 10 /EndOfLine true >>"
+        ));
+        assert_eq!(data, Compression::Group3.encode(&image));
+
+        // packbits streams end with the end of data marker
+        let (packbits, data) = dictionary(Compression::PackBits);
+        assert!(packbits.contains("/Filter /RunLengthDecode /Decode [1 0]"));
+        assert_eq!(data.last(), Some(&128));
+        pixels(&packbits, Compression::PackBits, &data);
+
+        let (deflate, data) = dictionary(Compression::Deflate);
+        assert!(deflate.contains("/Filter /FlateDecode /Decode [1 0]"));
+        assert!(
+            deflate.contains("/Width 20 /Height 10 /ColorSpace /DeviceGray /BitsPerComponent 1")
+        );
+        pixels(&deflate, Compression::Deflate, &data);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::contour::Contour;
+use std::fmt::Write;
+
+// turns sharper than this (in radians) stay corners when smoothing
+const CORNER_ANGLE: f64 = 1.3;
+
+pub fn path_data(contours: &[Contour], smoothing: f64) -> String {
+    let mut data = String::new();
+    for contour in contours {
+        let points = contour.points();
+        let n = points.len();
+        if n < 3 {
+            continue;
+        }
+        write!(data, "M{} {}", points[0].0, points[0].1).unwrap();
+        if smoothing <= 0.0 {
+            for (x, y) in &points[1..] {
+                write!(data, "L{x} {y}").unwrap();
+            }
+        } else {
+            let tangents = (0..n)
+                .map(|i| tangent(points[(i + n - 1) % n], points[i], points[(i + 1) % n]))
+                .collect::<Vec<(f64, f64)>>();
+            for i in 0..n {
+                let (x0, y0) = points[i];
+                let (x1, y1) = points[(i + 1) % n];
+                let (tx0, ty0) = tangents[i];
+                let (tx1, ty1) = tangents[(i + 1) % n];
+                let s = smoothing / 6.0;
+                write!(
+                    data,
+                    "C{:.2} {:.2} {:.2} {:.2} {} {}",
+                    x0 + tx0 * s,
+                    y0 + ty0 * s,
+                    x1 - tx1 * s,
+                    y1 - ty1 * s,
+                    x1,
+                    y1
+                )
+                .unwrap();
+            }
+        }
+        data.push('Z');
+    }
+    data
+}
+
+fn tangent(previous: (f64, f64), point: (f64, f64), next: (f64, f64)) -> (f64, f64) {
+    let incoming = (point.0 - previous.0).atan2(point.1 - previous.1);
+    let outgoing = (next.0 - point.0).atan2(next.1 - point.1);
+    let mut turn = (outgoing - incoming).abs();
+    if turn > std::f64::consts::PI {
+        turn = 2.0 * std::f64::consts::PI - turn;
+    }
+    if turn > CORNER_ANGLE {
+        (0.0, 0.0)
+    } else {
+        (next.0 - previous.0, next.1 - previous.1)
+    }
+}
+
+pub fn encode_svg(width: usize, height: usize, objects: &[Vec<Contour>], smoothing: f64) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    );
+    for contours in objects {
+        writeln!(
+            svg,
+            "<path fill=\"black\" fill-rule=\"evenodd\" d=\"{}\"/>",
+            path_data(contours, smoothing)
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
@@ -0,0 +1,164 @@
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    Fixed(f64),
+    Auto(Method),
+    Local(Local),
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "auto" | "otsu" => Ok(Self::Auto(Method::Otsu)),
+            "kittler" => Ok(Self::Auto(Method::Kittler)),
+            "sauvola" => Ok(Self::Local(Local::Sauvola)),
+            "niblack" => Ok(Self::Local(Local::Niblack)),
+            "bradley" => Ok(Self::Local(Local::Bradley)),
+            _ => value.parse::<f64>().map(Self::Fixed).map_err(|_| {
+                format!("expected a number between 0 and 1, auto, otsu, kittler, sauvola, niblack or bradley, got {value}")
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Otsu,
+    Kittler,
+}
+
+impl Method {
+    // the last bin of the histogram that still gets black
+    pub fn level(&self, histogram: &[usize]) -> usize {
+        match self {
+            Self::Otsu => otsu(histogram),
+            Self::Kittler => kittler(histogram).unwrap_or_else(|| otsu(histogram)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Local {
+    Sauvola,
+    Niblack,
+    Bradley,
+}
+
+impl Local {
+    pub fn default_k(&self) -> f64 {
+        match self {
+            Self::Sauvola => 0.2,
+            Self::Niblack => 0.2,
+            Self::Bradley => 0.15,
+        }
+    }
+
+    // the threshold of a pixel from the mean and standard deviation of its window
+    pub fn threshold(&self, mean: f64, deviation: f64, k: f64) -> f64 {
+        match self {
+            // 0.5 is the largest possible deviation of values between 0 and 1
+            Self::Sauvola => mean * (1.0 + k * (deviation / 0.5 - 1.0)),
+            Self::Niblack => mean - k * deviation,
+            Self::Bradley => mean * (1.0 - k),
+        }
+    }
+}
+
+// the sums of the weights, of the weighted bins and of the weighted squared bins up to every bin
+fn moments(histogram: &[usize]) -> Vec<(f64, f64, f64)> {
+    histogram
+        .iter()
+        .enumerate()
+        .scan((0.0, 0.0, 0.0), |(count, sum, squares), (idx, weight)| {
+            let weight = *weight as f64;
+            let idx = idx as f64;
+            *count += weight;
+            *sum += weight * idx;
+            *squares += weight * idx * idx;
+            Some((*count, *sum, *squares))
+        })
+        .collect()
+}
+
+fn otsu(histogram: &[usize]) -> usize {
+    let moments = moments(histogram);
+    let (total, sum, _) = moments.last().copied().unwrap_or_default();
+    let mut best = (0, 0.0);
+    for (idx, (count, partial, _)) in moments.iter().enumerate() {
+        let rest = total - count;
+        if *count == 0.0 || rest == 0.0 {
+            continue;
+        }
+        let mean_difference = partial / count - (sum - partial) / rest;
+        let variance = count * rest * mean_difference * mean_difference;
+        if variance > best.1 {
+            best = (idx, variance);
+        }
+    }
+    best.0
+}
+
+// minimum error thresholding, fitting a normal distribution to each side
+fn kittler(histogram: &[usize]) -> Option<usize> {
+    let moments = moments(histogram);
+    let (total, sum, squares) = moments.last().copied().unwrap_or_default();
+    let mut best: Option<(usize, f64)> = None;
+    for (idx, (count, partial, partial_squares)) in moments.iter().enumerate() {
+        let rest = total - count;
+        if *count == 0.0 || rest == 0.0 {
+            continue;
+        }
+        let mean = partial / count;
+        let variance = partial_squares / count - mean * mean;
+        let rest_mean = (sum - partial) / rest;
+        let rest_variance = (squares - partial_squares) / rest - rest_mean * rest_mean;
+        if variance <= 0.0 || rest_variance <= 0.0 {
+            continue;
+        }
+        let (p, q) = (count / total, rest / total);
+        let error = p * variance.ln() + q * rest_variance.ln() - 2.0 * (p * p.ln() + q * q.ln());
+        if best.is_none_or(|(_, min)| error < min) {
+            best = Some((idx, error));
+        }
+    }
+    best.map(|(idx, _)| idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bimodal() -> Vec<usize> {
+        let mut histogram = vec![0; 256];
+        for (center, weight) in [(40, 100.0), (200, 400.0)] {
+            for (idx, value) in histogram.iter_mut().enumerate() {
+                let x = (idx as f64 - center as f64) / 12.0;
+                *value += (weight * (-x * x / 2.0).exp()) as usize;
+            }
+        }
+        histogram
+    }
+
+    #[test]
+    fn otsu_splits_modes() {
+        let level = Method::Otsu.level(&bimodal());
+        assert!(level > 70 && level < 170, "{level}");
+    }
+
+    #[test]
+    fn kittler_splits_modes() {
+        let level = Method::Kittler.level(&bimodal());
+        assert!(level > 70 && level < 170, "{level}");
+    }
+
+    #[test]
+    fn parse_threshold() {
+        assert_eq!("0.8".parse(), Ok(Threshold::Fixed(0.8)));
+        assert_eq!("auto".parse(), Ok(Threshold::Auto(Method::Otsu)));
+        assert_eq!("sauvola".parse(), Ok(Threshold::Local(Local::Sauvola)));
+        assert!("sharp".parse::<Threshold>().is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use crate::encoder::{CcittEncoder, CcittMode, PackBitsEncoder};
+use crate::image::Image;
+use clap::ValueEnum;
+use flate2::write::ZlibEncoder;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    /// CCITT T.6 (Group 4)
+    Group4,
+    /// CCITT T.4 (Group 3, one-dimensional)
+    Group3,
+    /// Run-length encoded bytes
+    PackBits,
+    /// Zlib compressed bytes
+    Deflate,
+}
+
+impl Compression {
+    fn tag(&self) -> u16 {
+        match self {
+            Self::Group4 => 4,
+            Self::Group3 => 3,
+            Self::PackBits => 32773,
+            Self::Deflate => 8,
+        }
+    }
+
+    // undoes the byte codecs to the packed rows of an image, None for the fax codecs
+    #[cfg(test)]
+    pub fn unpack(&self, data: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::Group4 | Self::Group3 => None,
+            Self::PackBits => {
+                let mut bytes = Vec::new();
+                let mut data = data.iter().copied();
+                while let Some(header) = data.next() {
+                    match header {
+                        0..=127 => bytes.extend(data.by_ref().take(header as usize + 1)),
+                        // the end of data marker of pdf, tiff never gets it
+                        128 => break,
+                        _ => {
+                            let byte = data.next()?;
+                            bytes.extend(std::iter::repeat_n(byte, 257 - header as usize));
+                        }
+                    }
+                }
+                Some(bytes)
+            }
+            Self::Deflate => {
+                use std::io::Read;
+                let mut bytes = Vec::new();
+                flate2::read::ZlibDecoder::new(data)
+                    .read_to_end(&mut bytes)
+                    .ok()?;
+                Some(bytes)
+            }
+        }
+    }
+
+    pub fn encode(&self, image: &Image) -> Vec<u8> {
+        match self {
+            Self::Group4 | Self::Group3 => {
+                let mode = if *self == Self::Group4 {
+                    CcittMode::Group4
+                } else {
+                    CcittMode::Group3
+                };
+                let mut encoder = CcittEncoder::new(image.width(), mode);
+                for y in 0..image.height() {
+                    encoder.encode_line(image.line(y));
+                }
+                encoder.finish()
+            }
+            Self::PackBits | Self::Deflate => {
+                let mut data = Vec::new();
+                for y in 0..image.height() {
+                    let bytes = image.packed_line(y);
+                    if *self == Self::PackBits {
+                        data.extend(PackBitsEncoder::new(&bytes).flatten());
+                    } else {
+                        data.extend(bytes);
+                    }
+                }
+                if *self == Self::Deflate {
+                    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+                    encoder.write_all(&data).unwrap();
+                    data = encoder.finish().unwrap();
+                }
+                data
+            }
+        }
+    }
+}
+
+enum Value {
+    Short(u16),
+    Shorts(u16, u16),
+    Long(u32),
+    Rational(u32, u32),
+}
+
+pub fn encode_tiff(images: &[&Image], compression: Compression) -> Vec<u8> {
+    let mut data = b"II*\0".to_vec();
+    let mut next_ifd_pointer = data.len();
+    data.extend([0; 4]);
+
+    for (page, image) in images.iter().enumerate() {
+        let strip_offset = data.len() as u32;
+        let strip = compression.encode(image);
+        let strip_len = strip.len() as u32;
+        data.extend(strip);
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+
+        let rational = |dpi: f64| {
+            if dpi.fract() == 0.0 {
+                Value::Rational(dpi as u32, 1)
+            } else {
+                Value::Rational((dpi * 100.0).round() as u32, 100)
+            }
+        };
+        let (xdpi, ydpi) = image.dpi();
+        let mut tags = vec![
+            (254, Value::Long(if images.len() > 1 { 2 } else { 0 })),
+            (256, Value::Long(image.width() as u32)),
+            (257, Value::Long(image.height() as u32)),
+            (258, Value::Short(1)),
+            (259, Value::Short(compression.tag())),
+            (262, Value::Short(0)),
+            (273, Value::Long(strip_offset)),
+            (277, Value::Short(1)),
+            (278, Value::Long(image.height() as u32)),
+            (279, Value::Long(strip_len)),
+            (282, rational(xdpi)),
+            (283, rational(ydpi)),
+        ];
+        match compression {
+            Compression::Group3 => tags.push((292, Value::Long(0))),
+            Compression::Group4 => tags.push((293, Value::Long(0))),
+            Compression::PackBits | Compression::Deflate => (),
+        }
+        tags.push((296, Value::Short(2)));
+        if images.len() > 1 {
+            tags.push((297, Value::Shorts(page as u16, images.len() as u16)));
+        }
+
+        let ifd_offset = data.len() as u32;
+        data[next_ifd_pointer..next_ifd_pointer + 4].copy_from_slice(&ifd_offset.to_le_bytes());
+        let mut extra_offset = ifd_offset + 2 + tags.len() as u32 * 12 + 4;
+        let mut extra = Vec::new();
+        data.extend((tags.len() as u16).to_le_bytes());
+        for (tag, value) in tags {
+            data.extend(u16::to_le_bytes(tag));
+            match value {
+                Value::Short(value) => {
+                    data.extend([3, 0, 1, 0, 0, 0]);
+                    data.extend(value.to_le_bytes());
+                    data.extend([0, 0]);
+                }
+                Value::Shorts(first, second) => {
+                    data.extend([3, 0, 2, 0, 0, 0]);
+                    data.extend(first.to_le_bytes());
+                    data.extend(second.to_le_bytes());
+                }
+                Value::Long(value) => {
+                    data.extend([4, 0, 1, 0, 0, 0]);
+                    data.extend(value.to_le_bytes());
+                }
+                Value::Rational(numerator, denominator) => {
+                    data.extend([5, 0, 1, 0, 0, 0]);
+                    data.extend(extra_offset.to_le_bytes());
+                    extra.extend(numerator.to_le_bytes());
+                    extra.extend(denominator.to_le_bytes());
+                    extra_offset += 8;
+                }
+            }
+        }
+        next_ifd_pointer = data.len();
+        data.extend([0; 4]);
+        data.extend(extra);
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    // the (tag, type, count, value or offset) entries of each ifd in the chain
+    fn ifds(data: &[u8]) -> Vec<Vec<(u16, u16, u32, u32)>> {
+        let mut ifds = Vec::new();
+        let mut offset = u32_at(data, 4) as usize;
+        while offset != 0 {
+            assert_eq!(offset % 2, 0);
+            let count = u16_at(data, offset) as usize;
+            let entries = (0..count)
+                .map(|idx| {
+                    let entry = offset + 2 + idx * 12;
+                    (
+                        u16_at(data, entry),
+                        u16_at(data, entry + 2),
+                        u32_at(data, entry + 4),
+                        u32_at(data, entry + 8),
+                    )
+                })
+                .collect();
+            ifds.push(entries);
+            offset = u32_at(data, offset + 2 + count * 12) as usize;
+        }
+        ifds
+    }
+
+    fn value(ifd: &[(u16, u16, u32, u32)], tag: u16) -> Option<u32> {
+        ifd.iter().find(|entry| entry.0 == tag).map(|entry| entry.3)
+    }
+
+    #[test]
+    fn ifd_of_single_page() {
+        let image = Image::crossed(21, 9);
+        let tiff = encode_tiff(&[&image], Compression::Group4);
+        assert!(tiff.starts_with(b"II*\0"));
+
+        let ifds = ifds(&tiff);
+        assert_eq!(ifds.len(), 1);
+        let ifd = &ifds[0];
+        let tags: Vec<u16> = ifd.iter().map(|entry| entry.0).collect();
+        assert!(tags.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            tags,
+            [254, 256, 257, 258, 259, 262, 273, 277, 278, 279, 282, 283, 293, 296]
+        );
+        assert_eq!(value(ifd, 254), Some(0));
+        assert_eq!(value(ifd, 256), Some(21));
+        assert_eq!(value(ifd, 257), Some(9));
+        assert_eq!(value(ifd, 259), Some(4));
+        assert_eq!(value(ifd, 262), Some(0));
+        assert_eq!(value(ifd, 278), Some(9));
+
+        // the strip holds the encoded image
+        let offset = value(ifd, 273).unwrap() as usize;
+        let len = value(ifd, 279).unwrap() as usize;
+        assert_eq!(offset, 8);
+        assert_eq!(
+            tiff[offset..offset + len],
+            Compression::Group4.encode(&image)
+        );
+
+        // the resolution is a rational stored after the ifd
+        for tag in [282, 283] {
+            let entry = ifd.iter().find(|entry| entry.0 == tag).unwrap();
+            assert_eq!((entry.1, entry.2), (5, 1));
+            let offset = entry.3 as usize;
+            assert_eq!((u32_at(&tiff, offset), u32_at(&tiff, offset + 4)), (300, 1));
+        }
+    }
+
+    #[test]
+    fn ifd_chain_of_pages() {
+        let (first, second) = (Image::crossed(13, 7), Image::crossed(40, 3));
+        let images = [&first, &second];
+        for compression in [
+            Compression::Group3,
+            Compression::PackBits,
+            Compression::Deflate,
+        ] {
+            let tiff = encode_tiff(&images, compression);
+            let ifds = ifds(&tiff);
+            assert_eq!(ifds.len(), 2);
+            for (page, (ifd, image)) in ifds.iter().zip(images).enumerate() {
+                assert_eq!(value(ifd, 254), Some(2));
+                assert_eq!(value(ifd, 256), Some(image.width() as u32));
+                assert_eq!(value(ifd, 257), Some(image.height() as u32));
+                assert_eq!(value(ifd, 259), Some(compression.tag() as u32));
+                assert_eq!(
+                    value(ifd, 292).is_some(),
+                    compression == Compression::Group3
+                );
+                assert_eq!(value(ifd, 293), None);
+
+                // two shorts, the page number and the number of pages
+                let entry = ifd.iter().find(|entry| entry.0 == 297).unwrap();
+                assert_eq!((entry.1, entry.2), (3, 2));
+                assert_eq!(entry.3, page as u32 | 2 << 16);
+
+                // white is zero, so set bits of the strip are black
+                let offset = value(ifd, 273).unwrap() as usize;
+                let len = value(ifd, 279).unwrap() as usize;
+                let strip = &tiff[offset..offset + len];
+                match compression.unpack(strip) {
+                    Some(packed) => {
+                        let (width, height) = (image.width(), image.height());
+                        let black_is_one = value(ifd, 262) == Some(0);
+                        let decoded = Image::from_packed(width, height, &packed, black_is_one);
+                        assert_eq!(packed.len(), width.div_ceil(8) * height);
+                        assert!((0..height).all(|y| decoded.line(y).eq(image.line(y))));
+                    }
+                    None => assert_eq!(strip, compression.encode(image)),
+                }
+            }
+        }
+    }
+}